@@ -1,6 +1,15 @@
-use crate::solvers::*;
+use crate::solvers::{
+    params::{Param, Params},
+    *,
+};
 use clap::Parser;
-use std::{fs, hint::black_box, time::Instant};
+use std::{
+    fs,
+    hint::black_box,
+    ops::{Range, RangeInclusive},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 mod solvers;
 fn read_input(day: &str) -> String {
@@ -9,45 +18,67 @@ fn read_input(day: &str) -> String {
         .unwrap_or_else(|e| panic!("could not read input file from {path} with error {e}"))
 }
 
-type SolverType = fn(String) -> String;
+type SolverType = fn(String, &Params) -> String;
+// solvers without parameters ignore them
+macro_rules! plain {
+    ($day:ident) => {
+        (
+            |input, _: &Params| $day::part1(input),
+            |input, _: &Params| $day::part2(input),
+        )
+    };
+}
 fn get_function_and_data(day: usize) -> ((SolverType, SolverType), String) {
     match day {
-        1 => ((day01::part1, day01::part2), read_input("01")),
-        2 => ((day02::part1, day02::part2), read_input("02")),
-        3 => ((day03::part1, day03::part2), read_input("03")),
-        4 => ((day04::part1, day04::part2), read_input("04")),
-        5 => ((day05::part1, day05::part2), read_input("05")),
-        6 => ((day06::part1, day06::part2), read_input("06")),
-        7 => ((day07::part1, day07::part2), read_input("07")),
-        8 => ((day08::part1, day08::part2), read_input("08")),
-        9 => ((day09::part1, day09::part2), read_input("09")),
-        10 => ((day10::part1, day10::part2), read_input("10")),
+        1 => (plain!(day01), read_input("01")),
+        2 => (plain!(day02), read_input("02")),
+        3 => (plain!(day03), read_input("03")),
+        4 => (plain!(day04), read_input("04")),
+        5 => (plain!(day05), read_input("05")),
+        6 => (plain!(day06), read_input("06")),
+        7 => (plain!(day07), read_input("07")),
+        8 => (plain!(day08), read_input("08")),
+        9 => (plain!(day09), read_input("09")),
+        10 => (plain!(day10), read_input("10")),
         11 => ((day11::part1, day11::part2), read_input("11")),
-        12 => ((day12::part1, day12::part2), read_input("12")),
+        12 => (plain!(day12), read_input("12")),
         13 => ((day13::part1, day13::part2), read_input("13")),
         14 => ((day14::part1, day14::part2), read_input("14")),
-        15 => ((day15::part1, day15::part2), read_input("15")),
+        15 => (plain!(day15), read_input("15")),
         16 => ((day16::part1, day16::part2), read_input("16")),
-        17 => ((day17::part1, day17::part2), read_input("17")),
+        17 => (plain!(day17), read_input("17")),
         18 => ((day18::part1, day18::part2), read_input("18")),
-        19 => ((day19::part1, day19::part2), read_input("19")),
+        19 => (plain!(day19), read_input("19")),
         20 => ((day20::part1, day20::part2), read_input("20")),
         21 => ((day21::part1, day21::part2), read_input("21")),
         22 => ((day22::part1, day22::part2), read_input("22")),
-        23 => ((day23::part1, day23::part2), read_input("23")),
-        24 => ((day24::part1, day24::part2), read_input("24")),
-        25 => ((day25::part1, day25::part2), read_input("25")),
+        23 => (plain!(day23), read_input("23")),
+        24 => (plain!(day24), read_input("24")),
+        25 => (plain!(day25), read_input("25")),
         _ => {
             todo!();
         }
     }
 }
-fn run_bench(day: usize, part: usize, f: SolverType, input: &String) {
+fn get_params(day: usize) -> &'static [Param] {
+    match day {
+        11 => day11::PARAMS,
+        13 => day13::PARAMS,
+        14 => day14::PARAMS,
+        16 => day16::PARAMS,
+        18 => day18::PARAMS,
+        20 => day20::PARAMS,
+        21 => day21::PARAMS,
+        22 => day22::PARAMS,
+        _ => &[],
+    }
+}
+fn run_bench(day: usize, part: usize, f: SolverType, input: &String, params: &Params) {
     let timer = Instant::now();
     let mut run_count = 0;
     const MIN_TIME_MILLIS: u128 = 750;
     while timer.elapsed().as_millis() < MIN_TIME_MILLIS {
-        black_box(f(black_box(input.clone())));
+        black_box(f(black_box(input.clone()), params));
         run_count += 1;
         if run_count > 3333 {
             break;
@@ -67,21 +98,338 @@ struct Args {
     // Run benchmarks instead of executing normally
     #[arg(short, long, default_value_t = false)]
     benchmark: bool,
+
+    // Export the day 24 circuit as a Graphviz DOT graph to this path
+    #[arg(long)]
+    dot: Option<PathBuf>,
+
+    // Export the day 24 circuit as a structural Verilog module to this path
+    #[arg(long)]
+    verilog: Option<PathBuf>,
+
+    // Render the day 14 robots for a range of seconds like 7000..7100, or a single second
+    #[arg(long, value_parser = parse_frames)]
+    frames: Option<Range<usize>>,
+
+    // Directory to write rendered frames to, instead of printing them. Images can't be printed, so they need one
+    #[arg(long, required_if_eq_any = [("format", "pgm"), ("format", "ppm")])]
+    out: Option<PathBuf>,
+
+    // Format of the rendered frames
+    #[arg(long, value_enum, default_value_t = day14::FrameFormat::Ascii)]
+    format: day14::FrameFormat,
+
+    // Overlay the quadrants used for the safety factor on rendered frames
+    #[arg(long, default_value_t = false)]
+    quadrants: bool,
+
+    // Print every step of the day 15 robot for the given part, like the puzzle examples.
+    // Parts past 2 widen the warehouse further
+    #[arg(long, value_parser = parse_replay_part)]
+    replay: Option<usize>,
+
+    // Show an optimal day 16 route and every tile on optimal routes over the maze
+    #[arg(long, default_value_t = false)]
+    route: bool,
+
+    // Set a parameter of the day being run, like --param blinks_p1=6. Can be repeated
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, String)>,
+
+    // List the parameters of each day with their defaults
+    #[arg(long, default_value_t = false)]
+    list_params: bool,
+
+    // Print how many day 11 stones there are and how many different values they have after each blink, up to this many
+    #[arg(long)]
+    growth: Option<u32>,
+
+    // Print the values of the day 11 stones after this many blinks, with the most common ones first
+    #[arg(long)]
+    distribution: Option<u32>,
+
+    // How many of the most common values to print with --distribution, or 0 to print every value in order
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    // List the presses and tokens for each day 13 claw machine, or why its prize can't be won
+    #[arg(long, default_value_t = false)]
+    claws: bool,
+
+    // Find the day 14 tree by the structure of each frame, as a cross-check for part 2
+    #[arg(long, default_value_t = false)]
+    find_tree: bool,
+
+    // Find the day 18 byte that cuts off the exit with the given strategy, timing it
+    #[arg(long, value_enum)]
+    cut_off: Option<day18::CutOff>,
+
+    // Draw the day 18 path for part 1 and the byte that finally cuts off the exit
+    #[arg(long, default_value_t = false)]
+    exit_path: bool,
+
+    // Animate the day 18 path rerouting as bytes fall, waiting this many milliseconds between frames
+    #[arg(long)]
+    fall: Option<u64>,
+
+    // List how many day 20 cheats save each amount of time
+    #[arg(long, default_value_t = false)]
+    savings: bool,
+
+    // Mark this many of the day 20 cheats that save the most time on the track
+    #[arg(long)]
+    best_cheats: Option<usize>,
+
+    // Print the buttons we press for each day 21 code with the robots of part 1, checked by replaying them
+    #[arg(long, default_value_t = false)]
+    presses: bool,
+
+    // List how many presses each day 21 code takes, its numeric part and its complexity, for both parts
+    #[arg(long, default_value_t = false)]
+    complexities: bool,
+
+    // Print the day 22 secret numbers that turn into each of the input's after a day of new secrets
+    #[arg(long, default_value_t = false)]
+    rewind: bool,
+}
+fn parse_frames(s: &str) -> Result<Range<usize>, String> {
+    let parse = |n: &str| {
+        n.parse::<usize>()
+            .map_err(|e| format!("invalid second {n}: {e}"))
+    };
+    match s.split_once("..") {
+        Some((start, end)) => Ok(parse(start)?..parse(end)?),
+        None => parse(s).map(|second| second..second + 1),
+    }
+}
+fn parse_replay_part(s: &str) -> Result<usize, String> {
+    // the part is how many cells wide boxes are
+    match s.parse::<usize>() {
+        Ok(0) => Err("the part has to be at least 1, as boxes can't be 0 cells wide".to_string()),
+        Ok(part) => Ok(part),
+        Err(e) => Err(format!("invalid part {s}: {e}")),
+    }
+}
+fn parse_param(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected key=value, got {s}"))
+}
+fn mode_days(args: &Args) -> RangeInclusive<usize> {
+    // the days whose parameters apply, in the same order main picks a mode
+    let day = if args.dot.is_some() || args.verilog.is_some() {
+        24
+    } else if args.replay.is_some() {
+        15
+    } else if args.growth.is_some() || args.distribution.is_some() {
+        11
+    } else if args.route {
+        16
+    } else if args.claws {
+        13
+    } else if args.find_tree {
+        14
+    } else if args.cut_off.is_some() || args.exit_path || args.fall.is_some() {
+        18
+    } else if args.savings || args.best_cheats.is_some() {
+        20
+    } else if args.presses || args.complexities {
+        21
+    } else if args.rewind {
+        22
+    } else if args.frames.is_some() {
+        14
+    } else {
+        return args.day.unwrap_or(1)..=args.day.unwrap_or(25);
+    };
+    day..=day
+}
+fn write_export(path: &PathBuf, contents: impl AsRef<[u8]>) {
+    fs::write(path, contents)
+        .unwrap_or_else(|e| panic!("could not write to {} with error {e}", path.display()));
 }
 fn main() {
     let args = Args::parse();
 
-    let from = args.day.unwrap_or(1);
-    let to = args.day.unwrap_or(25);
-    for day in from..=to {
+    if args.list_params {
+        for day in 1..=25 {
+            let declared = get_params(day);
+            if declared.is_empty() {
+                continue;
+            }
+            println!("Day {day:2}:");
+            for param in declared {
+                let setting = format!("{}={}", param.key, param.default);
+                println!("  {setting:<16} {}", param.help);
+            }
+        }
+        return;
+    }
+
+    let mut params = Params::default();
+    for (key, value) in &args.params {
+        params.set(key, value);
+    }
+    for (key, value) in &args.params {
+        let Some(param) = mode_days(&args)
+            .flat_map(get_params)
+            .find(|param| param.key == key)
+        else {
+            eprintln!("no day being run has a parameter called {key}, see --list-params");
+            std::process::exit(2);
+        };
+        if let Err(e) = (param.parse)(value) {
+            eprintln!("invalid value {value} for {key}: {e}");
+            std::process::exit(2);
+        }
+    }
+
+    if args.dot.is_some() || args.verilog.is_some() {
+        let input = read_input("24");
+        if let Some(path) = &args.dot {
+            write_export(path, day24::to_dot(&input));
+        }
+        if let Some(path) = &args.verilog {
+            write_export(path, day24::to_verilog(&input));
+        }
+        return;
+    }
+
+    if let Some(part) = args.replay {
+        println!("{}", day15::replay(read_input("15"), part));
+        return;
+    }
+
+    if let Some(blinks) = args.growth {
+        for (blink, (total, distinct)) in day11::growth(read_input("11"), blinks)
+            .into_iter()
+            .enumerate()
+        {
+            println!("Blink {blink}: {total} stones with {distinct} different values");
+        }
+        return;
+    }
+
+    if let Some(blinks) = args.distribution {
+        let stones = day11::distribution(read_input("11"), blinks);
+        println!(
+            "After {blinks} blinks there are {} stones with {} different values",
+            stones.total(),
+            stones.distinct()
+        );
+        let values = match args.top {
+            0 => stones.values().to_vec(),
+            k => stones.top(k),
+        };
+        for (value, count) in values {
+            println!("{count} stones of {value}");
+        }
+        return;
+    }
+
+    if args.route {
+        print!("{}", day16::show_route(read_input("16"), &params));
+        return;
+    }
+
+    if args.claws {
+        print!("{}", day13::show_report(read_input("13"), &params));
+        return;
+    }
+
+    if args.find_tree {
+        let input = read_input("14");
+        match day14::find_tree(input.clone(), &params) {
+            Some(day14::Detection { second, detector }) => println!(
+                "Day 14 tree: second {second} by {detector:?}, the variance method says {}",
+                day14::part2(input, &params)
+            ),
+            None => println!("Day 14 tree: no frame stands out"),
+        }
+        return;
+    }
+
+    if let Some(strategy) = args.cut_off {
+        let input = read_input("18");
+        let timer = Instant::now();
+        let byte = day18::cut_off(input, strategy, &params);
+        let millis = timer.elapsed().as_secs_f64() * 1000.0;
+        println!("Day 18 cut-off byte by {strategy:?}: {byte} in {millis:.2} ms");
+        return;
+    }
+
+    if args.exit_path {
+        print!("{}", day18::show_path(read_input("18"), &params));
+        return;
+    }
+
+    if let Some(delay) = args.fall {
+        for frame in day18::animate(read_input("18"), &params) {
+            // move the cursor home and clear the screen so each frame draws over the last
+            print!("\x1b[H\x1b[2J{frame}");
+            std::thread::sleep(Duration::from_millis(delay));
+        }
+        return;
+    }
+
+    if args.savings {
+        print!("{}", day20::savings_report(read_input("20"), &params));
+        return;
+    }
+
+    if let Some(count) = args.best_cheats {
+        print!(
+            "{}",
+            day20::show_best_cheats(read_input("20"), count, &params)
+        );
+        return;
+    }
+
+    if args.presses {
+        print!("{}", day21::show_presses(read_input("21"), &params));
+        return;
+    }
+
+    if args.complexities {
+        print!("{}", day21::complexity_report(read_input("21"), &params));
+        return;
+    }
+
+    if args.rewind {
+        for secret in day22::rewind(read_input("22"), &params) {
+            println!("{secret}");
+        }
+        return;
+    }
+
+    if let Some(seconds) = args.frames {
+        let frames = day14::render_frames(
+            read_input("14"),
+            seconds,
+            args.format,
+            args.quadrants,
+            &params,
+        );
+        for (second, frame) in frames {
+            if let Some(dir) = &args.out {
+                let name = format!("frame_{second:05}.{}", args.format.extension());
+                write_export(&dir.join(name), frame);
+            } else {
+                println!("Second {second}:\n{}", String::from_utf8_lossy(&frame));
+            }
+        }
+        return;
+    }
+
+    for day in mode_days(&args) {
         let ((p1, p2), input) = get_function_and_data(day);
         if args.benchmark {
-            run_bench(day, 1, p1, &input);
-            run_bench(day, 2, p2, &input);
+            run_bench(day, 1, p1, &input, &params);
+            run_bench(day, 2, p2, &input, &params);
             println!();
         } else {
-            println!("Day {day:2} part 1: {}", p1(input.clone()));
-            println!("Day {day:2} part 2: {}", p2(input.clone()));
+            println!("Day {day:2} part 1: {}", p1(input.clone(), &params));
+            println!("Day {day:2} part 2: {}", p2(input.clone(), &params));
         }
     }
 }
@@ -1,5 +1,23 @@
+use arrayvec::ArrayVec;
+use num_bigint::BigUint;
 use rustc_hash::FxHashMap;
 
+use super::params::{parses, Param, Params};
+
+const BLINKS_P1: Param = Param {
+    key: "blinks_p1",
+    default: "25",
+    help: "how many times the stones blink in part 1",
+    parse: parses::<u32>,
+};
+const BLINKS_P2: Param = Param {
+    key: "blinks_p2",
+    default: "75",
+    help: "how many times the stones blink in part 2",
+    parse: parses::<u32>,
+};
+pub const PARAMS: &[Param] = &[BLINKS_P1, BLINKS_P2];
+
 fn split_number_digitwise(n: u64, digit_count: u32) -> (u64, u64) {
     let pow = 10u64.pow(digit_count / 2);
     let left = n / pow;
@@ -7,68 +25,180 @@ fn split_number_digitwise(n: u64, digit_count: u32) -> (u64, u64) {
     (left, right)
 }
 
-pub fn part1(input: String) -> String {
-    let stones = input
+fn parse_input(input: &str) -> Vec<u64> {
+    input
         .split_whitespace()
         .map(|word| word.parse::<u64>().unwrap())
-        .collect::<Vec<u64>>();
-    const N_ITER: u32 = 25;
-    iter_each(stones, N_ITER).to_string()
+        .collect()
 }
 
-fn iter_each(stones: Vec<u64>, max_iter: u32) -> u64 {
-    let mut memo = FxHashMap::default();
+/// Anything we can count stones with
+trait Count: Clone + Default {
+    fn one() -> Self;
+    fn add(&mut self, other: &Self);
+}
 
-    let mut count = 0u64;
-    for origin_stone in stones.iter() {
-        count += iterate_single_stone(*origin_stone, max_iter, &mut memo, 0);
-        memo.clear();
+impl Count for u128 {
+    fn one() -> Self {
+        1
+    }
+
+    fn add(&mut self, other: &Self) {
+        *self = self
+            .checked_add(*other)
+            .expect("too many stones to count in a u128");
     }
-    count
 }
 
-fn iterate_single_stone(
-    stone: u64,
-    iter_max: u32,
-    mut map: &mut FxHashMap<(u64, u32), u64>,
-    // should start from zero
-    iteration: u32,
-) -> u64 {
-    // recursion with memoization. We save (stone, iteration) pairs
-    // that map to the amount of stones it produces at iteration iter_max
-    if iteration == iter_max {
-        return 1;
+impl Count for BigUint {
+    fn one() -> Self {
+        BigUint::from(1u32)
     }
 
-    if let Some(&early_res) = map.get(&(stone, iteration)) {
-        return early_res;
+    fn add(&mut self, other: &Self) {
+        *self += other;
     }
+}
 
-    let res;
-    if stone == 0 {
-        // Stone is 0 => stone replaced by 1
-        res = iterate_single_stone(1, iter_max, &mut map, iteration + 1);
-    } else if (stone.ilog10() + 1) % 2 == 0 {
-        // Stone digit count is even => Split in two, left half of digits on left one, right on right.
-        let (l, r) = split_number_digitwise(stone, stone.ilog10() + 1);
+/// What each stone turns into when blinking, which is shared by every
+/// generation as the same few values keep coming up
+#[derive(Default)]
+struct Transitions {
+    cache: FxHashMap<u64, ArrayVec<u64, 2>>,
+}
 
-        res = iterate_single_stone(l, iter_max, &mut map, iteration + 1)
-            + iterate_single_stone(r, iter_max, &mut map, iteration + 1);
-    } else {
-        // Otherwise => stone replace by multiplying it by 2024
-        res = iterate_single_stone(stone * 2024, iter_max, &mut map, iteration + 1);
+impl Transitions {
+    fn next(&mut self, stone: u64) -> &[u64] {
+        self.cache.entry(stone).or_insert_with(|| {
+            let mut next = ArrayVec::new();
+            if stone == 0 {
+                // Stone is 0 => stone replaced by 1
+                next.push(1);
+            } else if (stone.ilog10() + 1).is_multiple_of(2) {
+                // Stone digit count is even => Split in two, left half of digits on left one, right on right.
+                let (l, r) = split_number_digitwise(stone, stone.ilog10() + 1);
+                next.push(l);
+                next.push(r);
+            } else {
+                // Otherwise => stone replace by multiplying it by 2024
+                next.push(stone.checked_mul(2024).expect("stone too large for a u64"));
+            }
+            next
+        })
     }
-    map.insert((stone, iteration), res);
-    res
 }
 
-pub fn part2(input: String) -> String {
-    let stones = input
-        .split_whitespace()
-        .map(|word| word.parse::<u64>().unwrap())
-        .collect::<Vec<u64>>();
-    const N_ITER: u32 = 75;
-    iter_each(stones, N_ITER).to_string()
+/// How many stones there are of each value. The order of the stones never
+/// affects what they turn into, so we only need to know how many of each
+/// value there are
+#[derive(Clone)]
+struct Stones<C> {
+    counts: FxHashMap<u64, C>,
+}
+
+impl<C: Count> Stones<C> {
+    fn new(stones: &[u64]) -> Stones<C> {
+        let mut counts = FxHashMap::<u64, C>::default();
+        for &stone in stones {
+            counts.entry(stone).or_default().add(&C::one());
+        }
+        Stones { counts }
+    }
+
+    fn blink(&self, transitions: &mut Transitions) -> Stones<C> {
+        let mut counts = FxHashMap::<u64, C>::default();
+        counts.reserve(self.counts.len());
+        for (&stone, count) in &self.counts {
+            for &next in transitions.next(stone) {
+                counts.entry(next).or_default().add(count);
+            }
+        }
+        Stones { counts }
+    }
+
+    fn total(&self) -> C {
+        let mut total = C::default();
+        for count in self.counts.values() {
+            total.add(count);
+        }
+        total
+    }
+
+    fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+}
+
+fn blink_times<C: Count>(stones: &[u64], blinks: u32) -> Stones<C> {
+    let mut transitions = Transitions::default();
+    let mut stones = Stones::new(stones);
+    for _ in 0..blinks {
+        stones = stones.blink(&mut transitions);
+    }
+    stones
+}
+
+/// How many stones there are and how many different values they have, for
+/// each generation from the start up to the given number of blinks
+pub fn growth(input: String, blinks: u32) -> Vec<(BigUint, usize)> {
+    let mut transitions = Transitions::default();
+    let mut stones = Stones::<BigUint>::new(&parse_input(&input));
+    let mut series = vec![(stones.total(), stones.distinct())];
+    for _ in 0..blinks {
+        stones = stones.blink(&mut transitions);
+        series.push((stones.total(), stones.distinct()));
+    }
+    series
+}
+
+/// The stones after some number of blinks, as each value with how many
+/// stones have it
+pub struct Distribution {
+    values: Vec<(u64, BigUint)>,
+}
+
+impl Distribution {
+    /// Every value with its count, smallest value first
+    pub fn values(&self) -> &[(u64, BigUint)] {
+        &self.values
+    }
+
+    pub fn total(&self) -> BigUint {
+        self.values.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn distinct(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The k values the most stones have, with ties going to the smaller value
+    pub fn top(&self, k: usize) -> Vec<(u64, BigUint)> {
+        let mut values = self.values.clone();
+        values.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        values.truncate(k);
+        values
+    }
+}
+
+pub fn distribution(input: String, blinks: u32) -> Distribution {
+    let stones = blink_times::<BigUint>(&parse_input(&input), blinks);
+    let mut values = stones.counts.into_iter().collect::<Vec<_>>();
+    values.sort_unstable_by_key(|&(value, _)| value);
+    Distribution { values }
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    let stones = parse_input(&input);
+    blink_times::<u128>(&stones, params.get(&BLINKS_P1))
+        .total()
+        .to_string()
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    let stones = parse_input(&input);
+    blink_times::<u128>(&stones, params.get(&BLINKS_P2))
+        .total()
+        .to_string()
 }
 
 #[cfg(test)]
@@ -78,18 +208,85 @@ mod tests {
     #[test]
     fn sample_p1() {
         let input = "125 17".to_string();
-        assert_eq!(part1(input), "55312");
+        assert_eq!(part1(input, &Params::default()), "55312");
 
         let input = "125".to_string();
-        assert_eq!(part1(input), "19025");
+        assert_eq!(part1(input, &Params::default()), "19025");
     }
 
     #[test]
     fn sample_p2() {
         let input = "125 17".to_string();
-        assert_eq!(part2(input), "65601038650482");
+        assert_eq!(part2(input, &Params::default()), "65601038650482");
 
         let input = "125".to_string();
-        assert_eq!(part2(input), "22840618691206");
+        assert_eq!(part2(input, &Params::default()), "22840618691206");
+    }
+
+    #[test]
+    fn sample_blinks() {
+        // the puzzle's longer example, one blink at a time
+        let mut params = Params::default();
+        for (blinks, expected) in [(1, "3"), (2, "4"), (3, "5"), (4, "9"), (5, "13"), (6, "22")] {
+            params.set("blinks_p1", blinks.to_string().as_str());
+            assert_eq!(part1("125 17".to_string(), &params), expected);
+        }
+    }
+
+    #[test]
+    fn growth_series() {
+        let series = growth("125 17".to_string(), 6);
+        let totals = series
+            .iter()
+            .map(|(total, _)| total.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(totals, ["2", "3", "4", "5", "9", "13", "22"]);
+        // the puzzle's example goes 125 17, then 253000 1 7, then
+        // 253 0 2024 14168, then 512072 1 20 24 28676032
+        let distinct = series.iter().map(|&(_, distinct)| distinct).take(4);
+        assert_eq!(distinct.collect::<Vec<_>>(), [2, 3, 4, 5]);
+
+        // counting with a u128 agrees until it runs out
+        let series = growth("125 17".to_string(), 1000);
+        let stones = blink_times::<u128>(&[125, 17], 150);
+        assert_eq!(series[150].0, BigUint::from(stones.total()));
+        assert!(series[1000].0.to_string().len() > 39);
+
+        // the values close on themselves long before then
+        assert_eq!(series[500].1, series[1000].1);
+    }
+
+    #[test]
+    fn value_distribution() {
+        // the puzzle's example after six blinks:
+        // 2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2
+        let stones = distribution("125 17".to_string(), 6);
+        assert_eq!(stones.total(), BigUint::from(22u32));
+        assert_eq!(stones.distinct(), 15);
+        let one = BigUint::from(1u32);
+        assert_eq!(stones.values()[0], (0, BigUint::from(2u32)));
+        assert_eq!(stones.values()[14], (2097446912, one.clone()));
+        let top = stones
+            .top(4)
+            .into_iter()
+            .map(|(value, count)| (value, count.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            top,
+            [
+                (2, "4".into()),
+                (0, "2".into()),
+                (6, "2".into()),
+                (40, "2".into())
+            ]
+        );
+        assert_eq!(stones.top(100).len(), 15);
+
+        // a single stone of 0 only ever has these values after enough blinks
+        let stones = distribution("0".to_string(), 200);
+        let values = stones.values().iter().map(|&(value, _)| value);
+        let later = distribution("0".to_string(), 201);
+        assert!(values.eq(later.values().iter().map(|&(value, _)| value)));
+        assert_eq!(stones.top(1)[0].0, 4);
     }
 }
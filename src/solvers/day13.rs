@@ -1,99 +1,355 @@
+use std::fmt;
+
+use itertools::Itertools;
 use regex::Regex;
 
-use super::util::float_basically_integer;
-
-fn gaussian_elimination(mut matrix: [[f64; 3]; 2]) -> (f64, f64) {
-    // our matrix is
-    // ax bx | x
-    // ay by | y
-
-    // multiplier: ay / ax
-    let f = matrix[1][0] / matrix[0][0];
-    // we negate f * R_1 from R_2, eliminating by
-
-    // ay -= (ay / ax) * ax <=> ay = 0
-    // by -= (ay / ax) * bx
-    // y  -= (ay / ax) * x
-    matrix[1][0] = 0.0;
-    matrix[1][1] -= f * matrix[0][1];
-    matrix[1][2] -= f * matrix[0][2];
-
-    // multiplier: bx / by
-    let f = matrix[0][1] / matrix[1][1];
-    // we negate f * R_2 from R_1, eliminating bx
-
-    // x  -= (bx / by) * y
-    // bx -= (bx / by) * by <=> bx = 0
-    // ax -= (bx / by) * ay
-    matrix[0][2] -= f * matrix[1][2];
-    matrix[0][1] = 0.0;
-    matrix[0][0] -= f * matrix[1][0];
-
-    // now we have to scale both rows so that positions ax and by are equal to 1,
-    // which gets us our answer in x and y
-    (matrix[0][2] / matrix[0][0], matrix[1][2] / matrix[1][1])
-}
-
-fn solve(input: String, constant: f64, threshold_exp: i32) -> u64 {
-    let button_re = Regex::new(r"Button .: X\+(\d+), Y\+(\d+)").unwrap();
-    let prize_re = Regex::new(r"Prize: X=(\d+), Y=(\d+)").unwrap();
-    input
-        .split("\n\n")
-        // trailing newline
-        .filter(|&it| it != "")
-        .map(|block| {
-            let mut it = block.lines();
-            let button_a = it.next().unwrap();
-            let button_b = it.next().unwrap();
-            let prize = it.next().unwrap();
-            let (_, [ax, ay]) = button_re.captures(button_a).unwrap().extract();
-            let (_, [bx, by]) = button_re.captures(button_b).unwrap().extract();
-            let (_, [x, y]) = prize_re.captures(prize).unwrap().extract();
-
-            let matrix = [
-                // augmented matrix
-                // ax bx | x
-                // ay by | y
-                [
-                    ax.parse().unwrap(),
-                    bx.parse().unwrap(),
-                    constant + x.parse::<f64>().unwrap(),
-                ],
-                [
-                    ay.parse().unwrap(),
-                    by.parse().unwrap(),
-                    constant + y.parse::<f64>().unwrap(),
-                ],
-            ];
-            gaussian_elimination(matrix)
-        })
-        .filter_map(|pair| {
-            // I wish I had an if-let chain :(
+use super::{
+    params::{parses, Param, Params},
+    util::extended_gcd,
+};
+
+const PRESS_CAP_P1: Param = Param {
+    key: "press_cap_p1",
+    default: "100",
+    help: "how many times each button can be pressed in part 1, or 0 for no limit",
+    parse: parses::<i128>,
+};
+const PRESS_CAP_P2: Param = Param {
+    key: "press_cap_p2",
+    default: "0",
+    help: "how many times each button can be pressed in part 2, or 0 for no limit",
+    parse: parses::<i128>,
+};
+pub const PARAMS: &[Param] = &[PRESS_CAP_P1, PRESS_CAP_P2];
+
+#[derive(Debug, Clone, Copy)]
+struct Machine {
+    a: (i128, i128),
+    b: (i128, i128),
+    prize: (i128, i128),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Presses {
+    pub a: i128,
+    pub b: i128,
+}
+
+impl Presses {
+    pub fn tokens(&self) -> i128 {
+        3 * self.a + self.b
+    }
+}
 
-            // threshold has to be adjustable as p2 numbers are too inaccurate,
-            float_basically_integer(pair.0, threshold_exp).and_then(|lhs| {
-                float_basically_integer(pair.1, threshold_exp).and_then(|rhs| Some(lhs * 3 + rhs))
+/// Why a prize can't be won
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unwinnable {
+    // the presses that reach the prize aren't whole numbers
+    NotIntegral,
+    // reaching the prize takes pressing a button a negative number of times
+    Negative,
+    // the buttons move the claw along a line that misses the prize
+    OffLine,
+    // reaching the prize takes pressing a button more than this many times
+    OverCap(i128),
+}
+
+impl fmt::Display for Unwinnable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unwinnable::NotIntegral => {
+                write!(f, "the presses that reach the prize aren't whole numbers")
+            }
+            Unwinnable::Negative => write!(f, "reaching the prize takes negative presses"),
+            Unwinnable::OffLine => {
+                write!(f, "the claw only moves along a line that misses the prize")
+            }
+            Unwinnable::OverCap(cap) => {
+                write!(
+                    f,
+                    "reaching the prize takes more than {cap} presses of a button"
+                )
+            }
+        }
+    }
+}
+
+impl Machine {
+    /// The presses that win the prize for the fewest tokens, pressing each
+    /// button at most `cap` times
+    fn solve(&self, cap: Option<i128>) -> Result<Presses, Unwinnable> {
+        let Machine { a, b, prize } = *self;
+        // with Cramer's rule, the only presses that reach the prize are
+        // a = (x by - y bx) / det and b = (ax y - ay x) / det
+        let det = a.0 * b.1 - a.1 * b.0;
+        if det != 0 {
+            let a_det = prize.0 * b.1 - prize.1 * b.0;
+            let b_det = a.0 * prize.1 - a.1 * prize.0;
+            if a_det % det != 0 || b_det % det != 0 {
+                return Err(Unwinnable::NotIntegral);
+            }
+            let presses = Presses {
+                a: a_det / det,
+                b: b_det / det,
+            };
+            if presses.a < 0 || presses.b < 0 {
+                return Err(Unwinnable::Negative);
+            }
+            return match cap {
+                Some(cap) if presses.a > cap || presses.b > cap => Err(Unwinnable::OverCap(cap)),
+                _ => Ok(presses),
+            };
+        }
+
+        // the buttons move the claw along the same line, so the prize has to
+        // be on it too, and then we only need to get there along one axis.
+        // we take x unless the line is the y axis, or the claw doesn't move
+        let cross = |(x, y): (i128, i128)| x * prize.1 - y * prize.0;
+        if cross(a) != 0 || cross(b) != 0 {
+            return Err(Unwinnable::OffLine);
+        }
+        let (p, q, r) = if a.0 != 0 || b.0 != 0 {
+            (a.0, b.0, prize.0)
+        } else if a.1 != 0 || b.1 != 0 {
+            (a.1, b.1, prize.1)
+        } else if prize == (0, 0) {
+            return Ok(Presses { a: 0, b: 0 });
+        } else {
+            return Err(Unwinnable::OffLine);
+        };
+        // a cap only rules out more presses, so we find out whether the
+        // prize can be reached at all first
+        let cheapest = solve_line(p, q, r, None)?;
+        match cap {
+            Some(cap) if cheapest.a > cap || cheapest.b > cap => solve_line(p, q, r, Some(cap)),
+            _ => Ok(cheapest),
+        }
+    }
+}
+
+fn div_ceil(n: i128, d: i128) -> i128 {
+    -((-n).div_euclid(d))
+}
+
+/// The cheapest presses with a p + b q = r when both buttons move the same
+/// way, pressing each button at most `cap` times. At least one of them has
+/// to move the claw
+fn solve_line(p: i128, q: i128, r: i128, cap: Option<i128>) -> Result<Presses, Unwinnable> {
+    let (g, s, t) = extended_gcd(p, q);
+    if r % g != 0 {
+        return Err(Unwinnable::NotIntegral);
+    }
+    // every solution is a = a0 + k q/g, b = b0 - k p/g, which costs
+    // 3 q/g - p/g more tokens for each step of k
+    let (a0, b0) = (s * (r / g), t * (r / g));
+    let (a_step, b_step) = (q / g, p / g);
+    let unreachable = Err(match cap {
+        Some(cap) => Unwinnable::OverCap(cap),
+        None => Unwinnable::Negative,
+    });
+
+    // the range of k that keeps both numbers of presses from going negative
+    // or past the cap. there is no limit on one side when a button doesn't
+    // move the claw, but then the cheapest way is the limit we do have
+    let mut k_min = None;
+    let mut k_max = None;
+    for (start, step, sign) in [(a0, a_step, 1), (b0, b_step, -1)] {
+        // the presses are start + sign k step
+        if step == 0 {
+            if start < 0 || cap.is_some_and(|cap| start > cap) {
+                return unreachable;
+            }
+            continue;
+        }
+        let (low, high) = (
+            div_ceil(-start, step),
+            cap.map(|cap| (cap - start).div_euclid(step)),
+        );
+        let (low, high) = if sign > 0 {
+            (Some(low), high)
+        } else {
+            (high.map(|high| -high), Some(-low))
+        };
+        k_min = k_min.max(low);
+        k_max = match (k_max, high) {
+            (Some(k_max), Some(high)) => Some(i128::min(k_max, high)),
+            (k_max, high) => k_max.or(high),
+        };
+    }
+    if let (Some(k_min), Some(k_max)) = (k_min, k_max) {
+        if k_min > k_max {
+            return unreachable;
+        }
+    }
+    let k = if 3 * a_step - b_step > 0 {
+        k_min.or(k_max)
+    } else {
+        k_max.or(k_min)
+    }
+    .unwrap();
+    Ok(Presses {
+        a: a0 + k * a_step,
+        b: b0 - k * b_step,
+    })
+}
+
+/// A machine in the input that isn't written the way the puzzle writes them
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    machine: usize,
+    reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "machine {}: {}", self.machine, self.reason)
+    }
+}
+
+fn parse_machine(
+    block: &[&str],
+    button_re: &Regex,
+    prize_re: &Regex,
+    offset: i128,
+) -> Result<Machine, String> {
+    // the buttons are small and the prizes don't get much larger than part
+    // 2 makes them, which keeps every product in the solver within an i128
+    let number = |n: &str, limit: i128| match n.parse::<i128>() {
+        Ok(n) if n <= limit => Ok(n),
+        _ => Err(format!("{n} is larger than {limit}")),
+    };
+    let button = |line: Option<&str>, name: &str| {
+        let line = line.ok_or_else(|| format!("button {name} is missing"))?;
+        match button_re.captures(line).map(|captures| captures.extract()) {
+            Some((_, [found, x, y])) if found == name => {
+                Ok((number(x, u32::MAX as i128)?, number(y, u32::MAX as i128)?))
+            }
+            _ => Err(format!(
+                "expected button {name} like `Button {name}: X+94, Y+34`, got `{line}`"
+            )),
+        }
+    };
+
+    let mut lines = block.iter().copied();
+    let a = button(lines.next(), "A")?;
+    let b = button(lines.next(), "B")?;
+    let line = lines.next().ok_or("the prize is missing")?;
+    let Some((_, [x, y])) = prize_re.captures(line).map(|captures| captures.extract()) else {
+        return Err(format!(
+            "expected the prize like `Prize: X=8400, Y=5400`, got `{line}`"
+        ));
+    };
+    let prize = (
+        offset + number(x, u64::MAX as i128)?,
+        offset + number(y, u64::MAX as i128)?,
+    );
+    if let Some(line) = lines.next() {
+        return Err(format!("unexpected `{line}` after the prize"));
+    }
+    Ok(Machine { a, b, prize })
+}
+
+fn parse_input(input: &str, offset: i128) -> Result<Vec<Machine>, ParseError> {
+    let button_re = Regex::new(r"^Button ([AB]): X\+(\d+), Y\+(\d+)$").unwrap();
+    let prize_re = Regex::new(r"^Prize: X=(\d+), Y=(\d+)$").unwrap();
+    // machines are separated by blank lines, of which there may be a few
+    let blocks = input
+        .lines()
+        .map(str::trim)
+        .chunk_by(|line| line.is_empty());
+    blocks
+        .into_iter()
+        .filter(|(blank, _)| !blank)
+        .enumerate()
+        .map(|(i, (_, block))| {
+            let block = block.collect::<Vec<_>>();
+            parse_machine(&block, &button_re, &prize_re, offset).map_err(|reason| ParseError {
+                machine: i + 1,
+                reason,
             })
         })
-        .sum::<u64>()
+        .collect()
 }
 
-pub fn part1(input: String) -> String {
-    solve(input, 0.0, -9).to_string()
+// part 2 moves every prize this far away along both axes
+const PART2_OFFSET: i128 = 10000000000000;
+
+/// The cheapest presses for each machine, or why its prize can't be won
+pub fn report(
+    input: &str,
+    part: usize,
+    params: &Params,
+) -> Result<Vec<Result<Presses, Unwinnable>>, ParseError> {
+    let (offset, cap) = match part {
+        1 => (0, params.get::<i128>(&PRESS_CAP_P1)),
+        _ => (PART2_OFFSET, params.get::<i128>(&PRESS_CAP_P2)),
+    };
+    let cap = (cap != 0).then_some(cap);
+    Ok(parse_input(input, offset)?
+        .iter()
+        .map(|machine| machine.solve(cap))
+        .collect())
 }
 
-pub fn part2(input: String) -> String {
-    // threshold has to be higher due to big numbers... kind of stupid
-    solve(input, 10000000000000.0, -3).to_string()
+/// How many tokens win every prize that can be won
+fn total_tokens(machines: &[Result<Presses, Unwinnable>]) -> i128 {
+    machines.iter().flatten().map(Presses::tokens).sum()
+}
+
+/// The presses and tokens for each machine in both parts, or why its prize
+/// can't be won
+pub fn show_report(input: String, params: &Params) -> String {
+    let mut output = String::new();
+    for part in [1, 2] {
+        output += &format!("Part {part}:\n");
+        let machines = match report(&input, part, params) {
+            Ok(machines) => machines,
+            Err(error) => {
+                output += &format!("{error}\n");
+                continue;
+            }
+        };
+        for (i, machine) in machines.iter().enumerate() {
+            output += &match machine {
+                Ok(presses) => format!(
+                    "Machine {}: {} A and {} B presses for {} tokens\n",
+                    i + 1,
+                    presses.a,
+                    presses.b,
+                    presses.tokens()
+                ),
+                Err(reason) => format!("Machine {}: no prize, {reason}\n", i + 1),
+            };
+        }
+        let won = machines.iter().flatten().count();
+        output += &format!(
+            "{} tokens win {won} of the {} prizes\n",
+            total_tokens(&machines),
+            machines.len()
+        );
+    }
+    output
+}
+
+fn solve(input: String, part: usize, params: &Params) -> i128 {
+    let machines = report(&input, part, params).unwrap_or_else(|error| panic!("{error}"));
+    total_tokens(&machines)
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    solve(input, 1, params).to_string()
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    solve(input, 2, params).to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solvers::util::XorShift;
 
-    #[test]
-    fn sample_p1() {
-        let input = "Button A: X+94, Y+34
+    const SAMPLE: &str = "Button A: X+94, Y+34
 Button B: X+22, Y+67
 Prize: X=8400, Y=5400
 
@@ -107,29 +363,157 @@ Prize: X=7870, Y=6450
 
 Button A: X+69, Y+23
 Button B: X+27, Y+71
-Prize: X=18641, Y=10279"
-            .to_string();
-        assert_eq!(part1(input), "480");
+Prize: X=18641, Y=10279";
+
+    #[test]
+    fn sample_p1() {
+        assert_eq!(part1(SAMPLE.to_string(), &Params::default()), "480");
     }
 
     #[test]
     fn sample_p2() {
-        let input = "Button A: X+94, Y+34
-Button B: X+22, Y+67
-Prize: X=8400, Y=5400
+        assert_eq!(
+            part2(SAMPLE.to_string(), &Params::default()),
+            "875318608908"
+        );
+    }
 
-Button A: X+26, Y+66
-Button B: X+67, Y+21
-Prize: X=12748, Y=12176
+    #[test]
+    fn sample_report() {
+        let params = Params::default();
+        assert_eq!(
+            report(SAMPLE, 1, &params),
+            Ok(vec![
+                Ok(Presses { a: 80, b: 40 }),
+                Err(Unwinnable::NotIntegral),
+                Ok(Presses { a: 38, b: 86 }),
+                Err(Unwinnable::NotIntegral),
+            ])
+        );
+        let won = report(SAMPLE, 2, &params)
+            .unwrap()
+            .iter()
+            .map(Result::is_ok)
+            .collect::<Vec<_>>();
+        assert_eq!(won, [false, true, false, true]);
 
-Button A: X+17, Y+86
-Button B: X+84, Y+37
-Prize: X=7870, Y=6450
+        // the second and fourth machines take far more presses than part 1 allows
+        let mut params = Params::default();
+        params.set("press_cap_p2", "100");
+        let machines = report(SAMPLE, 2, &params).unwrap();
+        assert_eq!(machines[1], Err(Unwinnable::OverCap(100)));
+        params.set("press_cap_p1", "50");
+        assert_eq!(part1(SAMPLE.to_string(), &params), "0");
+        params.set("press_cap_p1", "80");
+        assert_eq!(part1(SAMPLE.to_string(), &params), "280");
+    }
 
-Button A: X+69, Y+23
-Button B: X+27, Y+71
-Prize: X=18641, Y=10279"
-            .to_string();
-        assert_eq!(part2(input), "875318608908");
+    #[test]
+    fn parse_errors() {
+        let params = Params::default();
+        let error = |machine, reason: &str| {
+            Err(ParseError {
+                machine,
+                reason: reason.to_string(),
+            })
+        };
+
+        let swapped = SAMPLE.replacen("Button A: X+26", "Button B: X+26", 1);
+        assert_eq!(
+            report(&swapped, 1, &params),
+            error(
+                2,
+                "expected button A like `Button A: X+94, Y+34`, got `Button B: X+26, Y+66`"
+            )
+        );
+        let missing = SAMPLE.replacen("Prize: X=7870, Y=6450", "", 1);
+        assert_eq!(
+            report(&missing, 1, &params),
+            error(3, "the prize is missing")
+        );
+        let negative = SAMPLE.replacen("X+17", "X-17", 1);
+        assert!(report(&negative, 1, &params).is_err());
+        let large = SAMPLE.replacen("X+17", "X+17000000000", 1);
+        assert_eq!(
+            report(&large, 1, &params),
+            error(3, "17000000000 is larger than 4294967295")
+        );
+        let extra = SAMPLE.to_string() + "\nButton C: X+1, Y+1";
+        assert_eq!(
+            report(&extra, 1, &params),
+            error(4, "unexpected `Button C: X+1, Y+1` after the prize")
+        );
+        // windows line endings and extra blank lines are fine
+        let spaced = SAMPLE.replace('\n', "\r\n") + "\r\n\r\n";
+        assert_eq!(part1(spaced, &params), "480");
+    }
+
+    #[test]
+    fn buttons_along_one_line() {
+        let solve = |a, b, prize| Machine { a, b, prize }.solve(None);
+
+        // B goes three times as far for a third of the price of A
+        assert_eq!(solve((1, 1), (3, 3), (10, 10)), Ok(Presses { a: 1, b: 3 }));
+        // A goes four times as far for three times the price of B
+        assert_eq!(solve((4, 4), (1, 1), (7, 7)), Ok(Presses { a: 1, b: 3 }));
+        // every way costs the same
+        let presses = solve((3, 6), (1, 2), (10, 20)).unwrap();
+        assert_eq!(presses.tokens(), 10);
+        // off the line, or between the spots the buttons can reach
+        assert_eq!(solve((1, 1), (2, 2), (3, 4)), Err(Unwinnable::OffLine));
+        assert_eq!(solve((2, 2), (4, 4), (5, 5)), Err(Unwinnable::NotIntegral));
+        // a button that doesn't move the claw, and buttons along the y axis
+        assert_eq!(solve((0, 0), (2, 3), (4, 6)), Ok(Presses { a: 0, b: 2 }));
+        assert_eq!(solve((0, 1), (0, 2), (0, 5)), Ok(Presses { a: 1, b: 2 }));
+        assert_eq!(solve((0, 0), (0, 0), (0, 0)), Ok(Presses { a: 0, b: 0 }));
+        assert_eq!(solve((0, 0), (0, 0), (1, 0)), Err(Unwinnable::OffLine));
+        // the only way there goes backwards
+        assert_eq!(solve((2, 1), (1, 2), (1, 5)), Err(Unwinnable::Negative));
+
+        // with a cap the cheapest way may be out of reach, but not every way
+        let machine = Machine {
+            a: (1, 1),
+            b: (3, 3),
+            prize: (30, 30),
+        };
+        assert_eq!(machine.solve(Some(10)), Ok(Presses { a: 0, b: 10 }));
+        assert_eq!(machine.solve(Some(5)), Err(Unwinnable::OverCap(5)));
+        assert_eq!(machine.solve(Some(9)), Ok(Presses { a: 3, b: 9 }));
+    }
+
+    #[test]
+    fn against_every_press() {
+        // small machines where trying every number of presses is quick, with
+        // half of them having B along the same line as A
+        let mut rng = XorShift::new(13);
+        let mut random = |n: u64| rng.below(n) as i128;
+        for _ in 0..2000 {
+            let a = (1 + random(5), 1 + random(5));
+            let b = if random(2) == 0 {
+                let (scale, divide) = (1 + random(4), 1 + random(4));
+                ((a.0 * scale) / divide, (a.1 * scale) / divide)
+            } else {
+                (1 + random(5), 1 + random(5))
+            };
+            let prize = (random(60), random(60));
+            let machine = Machine { a, b, prize };
+
+            for cap in [None, Some(3 + random(20))] {
+                let limit = cap.unwrap_or(60);
+                let cheapest = (0..=limit)
+                    .flat_map(|a| (0..=limit).map(move |b| Presses { a, b }))
+                    .filter(|&presses| {
+                        machine.a.0 * presses.a + machine.b.0 * presses.b == prize.0
+                            && machine.a.1 * presses.a + machine.b.1 * presses.b == prize.1
+                    })
+                    .min_by_key(Presses::tokens);
+                let solved = machine.solve(cap);
+                assert_eq!(
+                    solved.ok().map(|presses| presses.tokens()),
+                    cheapest.map(|presses| presses.tokens()),
+                    "{machine:?} {cap:?}"
+                );
+            }
+        }
     }
 }
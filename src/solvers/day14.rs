@@ -1,8 +1,65 @@
-use std::i64;
+use std::ops::Range;
 
+use clap::ValueEnum;
 use regex::Regex;
 
-use crate::solvers::util::float_basically_integer;
+use crate::solvers::{
+    params::{parses, Param, Params},
+    util::extended_gcd,
+};
+
+const WIDTH: Param = Param {
+    key: "width",
+    default: "101",
+    help: "how many tiles wide the room is",
+    parse: parses::<i64>,
+};
+const HEIGHT: Param = Param {
+    key: "height",
+    default: "103",
+    help: "how many tiles tall the room is",
+    parse: parses::<i64>,
+};
+pub const PARAMS: &[Param] = &[WIDTH, HEIGHT];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FrameFormat {
+    Ascii,
+    Pgm,
+    Ppm,
+}
+
+impl FrameFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Ascii => "txt",
+            FrameFormat::Pgm => "pgm",
+            FrameFormat::Ppm => "ppm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    width: i64,
+    height: i64,
+}
+
+impl Room {
+    fn new(params: &Params) -> Room {
+        let room = Room {
+            width: params.get(&WIDTH),
+            height: params.get(&HEIGHT),
+        };
+        assert!(
+            room.width >= 1 && room.height >= 1,
+            "the room has to be at least 1 by 1, not {} by {}",
+            room.width,
+            room.height
+        );
+        room
+    }
+}
 
 struct Robot {
     x: i64,
@@ -20,17 +77,13 @@ enum Quadrant {
 }
 
 impl Robot {
-    const H: i64 = 103;
-    const W: i64 = 101;
-    fn iter_by(&mut self, n: usize) {
-        let nx = self.x + n as i64 * self.v_x;
-        self.x = (nx % 101 + 101) % 101;
-        let ny = self.y + n as i64 * self.v_y;
-        self.y = (ny % 103 + 103) % 103;
-    }
-    fn get_quadrant(&self) -> Quadrant {
-        let middle_horizontal = Self::W / 2;
-        let middle_vertical = Self::H / 2;
+    fn iter_by(&mut self, n: usize, room: Room) {
+        self.x = (self.x + n as i64 * self.v_x).rem_euclid(room.width);
+        self.y = (self.y + n as i64 * self.v_y).rem_euclid(room.height);
+    }
+    fn get_quadrant(&self, room: Room) -> Quadrant {
+        let middle_horizontal = room.width / 2;
+        let middle_vertical = room.height / 2;
 
         if self.x == middle_horizontal || self.y == middle_vertical {
             // the middle horizontal and vertical row are not counted in the safety score
@@ -53,7 +106,7 @@ impl Robot {
     }
 }
 
-fn parse_input(input: String) -> Vec<Robot> {
+fn parse_input(input: String, room: Room) -> Vec<Robot> {
     let re = Regex::new(r"p=(\d+),(\d+) v=(-?\d+),(-?\d+)").unwrap();
 
     input
@@ -63,17 +116,119 @@ fn parse_input(input: String) -> Vec<Robot> {
             let (_, [px, py, vx, vy]) = caps.extract();
             let v_x = vx.parse().unwrap();
             let v_y = vy.parse().unwrap();
-            let x = px.parse().unwrap();
-            let y = py.parse().unwrap();
+            // robots wrap around the edges, so one starting outside a
+            // smaller room than the input's is where it would wrap to
+            let x = px.parse::<i64>().unwrap().rem_euclid(room.width);
+            let y = py.parse::<i64>().unwrap().rem_euclid(room.height);
             Robot { x, y, v_x, v_y }
         })
         .collect::<Vec<_>>()
 }
 
-fn calculate_scores(robots: &Vec<Robot>) -> i64 {
+fn occupancy(robots: &[Robot], room: Room) -> Vec<Vec<u32>> {
+    // robot count per tile, indexed by y and then x
+    let mut grid = vec![vec![0; room.width as usize]; room.height as usize];
+    for robot in robots {
+        grid[robot.y as usize][robot.x as usize] += 1;
+    }
+    grid
+}
+
+fn quadrant_at(x: i64, y: i64, room: Room) -> Quadrant {
+    Robot {
+        x,
+        y,
+        v_x: 0,
+        v_y: 0,
+    }
+    .get_quadrant(room)
+}
+
+fn render_ascii(robots: &[Robot], room: Room, quadrants: bool) -> String {
+    // same notation as the puzzle, where the quadrant overlay blanks out the
+    // middle row and column like the safety factor explanation does
+    let mut out = String::new();
+    for (y, row) in occupancy(robots, room).into_iter().enumerate() {
+        for (x, count) in row.into_iter().enumerate() {
+            let in_middle = matches!(quadrant_at(x as i64, y as i64, room), Quadrant::Middle);
+            out.push(match count {
+                _ if quadrants && in_middle => ' ',
+                0 => '.',
+                1..=9 => char::from_digit(count, 10).unwrap(),
+                _ => '*',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_image(robots: &[Robot], room: Room, format: FrameFormat, quadrants: bool) -> Vec<u8> {
+    // robots are white, and with the overlay each quadrant gets its own dim
+    // background colour, or shade of grey for pgm
+    let (magic, channels) = match format {
+        FrameFormat::Pgm => ("P5", 1),
+        FrameFormat::Ppm => ("P6", 3),
+        FrameFormat::Ascii => unreachable!(),
+    };
+    let mut out = format!("{magic}\n{} {}\n255\n", room.width, room.height).into_bytes();
+    for (y, row) in occupancy(robots, room).into_iter().enumerate() {
+        for (x, count) in row.into_iter().enumerate() {
+            let colour = if count > 0 {
+                [255, 255, 255]
+            } else if !quadrants {
+                [0, 0, 0]
+            } else {
+                match quadrant_at(x as i64, y as i64, room) {
+                    Quadrant::TopLeft => [96, 32, 32],
+                    Quadrant::TopRight => [32, 96, 32],
+                    Quadrant::BottomLeft => [32, 32, 96],
+                    Quadrant::BottomRight => [96, 96, 32],
+                    Quadrant::Middle => [0, 0, 0],
+                }
+            };
+            if channels == 1 {
+                out.push((colour.iter().map(|&c| c as u32).sum::<u32>() / 3) as u8);
+            } else {
+                out.extend(colour);
+            }
+        }
+    }
+    out
+}
+
+fn render(robots: &[Robot], room: Room, format: FrameFormat, quadrants: bool) -> Vec<u8> {
+    match format {
+        FrameFormat::Ascii => render_ascii(robots, room, quadrants).into_bytes(),
+        _ => render_image(robots, room, format, quadrants),
+    }
+}
+
+pub fn render_frames(
+    input: String,
+    seconds: Range<usize>,
+    format: FrameFormat,
+    quadrants: bool,
+    params: &Params,
+) -> impl Iterator<Item = (usize, Vec<u8>)> {
+    let room = Room::new(params);
+    let mut robots = parse_input(input, room);
+    for robot in robots.iter_mut() {
+        robot.iter_by(seconds.start, room);
+    }
+    seconds.map(move |second| {
+        let frame = render(&robots, room, format, quadrants);
+        for robot in robots.iter_mut() {
+            robot.iter_by(1, room);
+        }
+        (second, frame)
+    })
+}
+
+fn calculate_scores(robots: &[Robot], room: Room) -> i64 {
     let mut scores = [0; 4];
     robots.iter().for_each(|robot| {
-        match robot.get_quadrant() {
+        match robot.get_quadrant(room) {
             Quadrant::TopLeft => {
                 scores[0] += 1;
             }
@@ -94,14 +249,18 @@ fn calculate_scores(robots: &Vec<Robot>) -> i64 {
     scores.into_iter().reduce(|a, b| a * b).unwrap()
 }
 
-pub fn part1(input: String) -> String {
-    let mut robots = parse_input(input);
+fn safety_factor(input: String, room: Room) -> i64 {
+    let mut robots = parse_input(input, room);
 
     for robot in robots.iter_mut() {
-        robot.iter_by(100);
+        robot.iter_by(100, room);
     }
 
-    calculate_scores(&robots).to_string()
+    calculate_scores(&robots, room)
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    safety_factor(input, Room::new(params)).to_string()
 }
 fn vars(bots: &Vec<Robot>) -> (f64, f64) {
     let n = bots.len() as f64;
@@ -126,50 +285,195 @@ fn vars(bots: &Vec<Robot>) -> (f64, f64) {
         .unwrap()
 }
 
-pub fn part2(input: String) -> String {
-    let mut robots = parse_input(input);
+fn crt(a: i64, n: i64, b: i64, m: i64) -> Option<(i64, i64)> {
+    // solves t = a (mod n) and t = b (mod m), returning the smallest non-negative
+    // t along with the period lcm(n, m) it repeats with. when n and m aren't
+    // coprime, a solution only exists if a and b agree modulo gcd(n, m)
+    let (g, s, _) = extended_gcd(n as i128, m as i128);
+    let difference = (b - a) as i128;
+    if difference % g != 0 {
+        return None;
+    }
+    let lcm = n as i128 / g * m as i128;
+    // n * s = g (mod m), so stepping by n * k with k = s * difference / g
+    // takes us from a to b modulo m
+    let k = (s * (difference / g)).rem_euclid(m as i128 / g);
+    let t = (a as i128 + n as i128 * k).rem_euclid(lcm);
+    Some((t as i64, lcm as i64))
+}
 
+fn find_alignment(mut robots: Vec<Robot>, room: Room) -> Option<i64> {
     // the robots tend to group along the x or y axis, seemingly with periodicity equal to the width or height
     // and from these groupings we can observe that the variance in coordinates is minimal when it happens.
     // we want to find the offset of iteration count when this happens for x and y
     let mut x_grouping = (0, f64::MAX);
     let mut y_grouping = (0, f64::MAX);
-    for i in 1..103 {
-        for robot in robots.iter_mut() {
-            robot.iter_by(1);
-        }
-        // save minimum variance for x and y coordinates
+    for i in 0..room.width.max(room.height) {
+        // save minimum variance for x and y coordinates within their periods
         let (x_var, y_var) = vars(&robots);
-        if x_var < x_grouping.1 {
+        if i < room.width && x_var < x_grouping.1 {
             x_grouping = (i, x_var);
         }
-        if y_var < y_grouping.1 {
+        if i < room.height && y_var < y_grouping.1 {
             y_grouping = (i, y_var);
         }
+        for robot in robots.iter_mut() {
+            robot.iter_by(1, room);
+        }
     }
 
-    // now we have to determine when they group on the y AND x axis at the same time
-    // by solving: x_grouping.0 + 101x = y_grouping.0 + 103y for an integer answer
-    let mut y = 3;
-    loop {
-        let x = (y_grouping.0 as f64 + 103.0 * y as f64 - x_grouping.0 as f64) / 101.0;
-        if float_basically_integer(x, -9).is_some() {
-            break;
+    // now we have to determine when they group on the y AND x axis at the same time,
+    // which is when t = x_grouping.0 (mod width) and t = y_grouping.0 (mod height)
+    crt(x_grouping.0, room.width, y_grouping.0, room.height).map(|(t, _)| t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detector {
+    LargestComponent,
+    Entropy,
+    DistinctTiles,
+}
+
+impl Detector {
+    const ALL: [Detector; 3] = [
+        Detector::LargestComponent,
+        Detector::Entropy,
+        Detector::DistinctTiles,
+    ];
+    // entropy is measured over blocks of this size, as almost every robot is
+    // on its own tile anyway
+    const ENTROPY_BLOCK: usize = 4;
+
+    fn score(&self, grid: &[Vec<u32>], robot_count: usize) -> f64 {
+        // higher scores mean more structure
+        match self {
+            Detector::LargestComponent => largest_component(grid) as f64,
+            Detector::Entropy => {
+                let mut blocks = vec![
+                    vec![0u32; grid[0].len().div_ceil(Self::ENTROPY_BLOCK)];
+                    grid.len().div_ceil(Self::ENTROPY_BLOCK)
+                ];
+                for (y, row) in grid.iter().enumerate() {
+                    for (x, &count) in row.iter().enumerate() {
+                        blocks[y / Self::ENTROPY_BLOCK][x / Self::ENTROPY_BLOCK] += count;
+                    }
+                }
+                let entropy = blocks
+                    .into_iter()
+                    .flatten()
+                    .filter(|&count| count > 0)
+                    .map(|count| {
+                        let p = count as f64 / robot_count as f64;
+                        -p * p.log2()
+                    })
+                    .sum::<f64>();
+                -entropy
+            }
+            Detector::DistinctTiles => grid.iter().flatten().filter(|&&it| it > 0).count() as f64,
         }
-        y += 1;
     }
+}
 
-    // add discovered offset to y times periodicity of 103
-    (y * 103 + y_grouping.0).to_string()
+fn largest_component(grid: &[Vec<u32>]) -> usize {
+    // flood fill over occupied tiles, counting tiles rather than robots
+    let (h, w) = (grid.len(), grid[0].len());
+    let mut seen = vec![vec![false; w]; h];
+    let mut largest = 0;
+    for y in 0..h {
+        for x in 0..w {
+            if grid[y][x] == 0 || seen[y][x] {
+                continue;
+            }
+            seen[y][x] = true;
+            let mut stack = vec![(y, x)];
+            let mut size = 0;
+            while let Some((y, x)) = stack.pop() {
+                size += 1;
+                let neighbours = [
+                    (y.wrapping_sub(1), x),
+                    (y + 1, x),
+                    (y, x.wrapping_sub(1)),
+                    (y, x + 1),
+                ];
+                for (ny, nx) in neighbours {
+                    if ny < h && nx < w && grid[ny][nx] > 0 && !seen[ny][nx] {
+                        seen[ny][nx] = true;
+                        stack.push((ny, nx));
+                    }
+                }
+            }
+            largest = largest.max(size);
+        }
+    }
+    largest
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub second: i64,
+    pub detector: Detector,
+}
+
+fn detect_tree(mut robots: Vec<Robot>, room: Room) -> Option<Detection> {
+    // unlike the variance approach, this scores every distinct frame by how
+    // much structure it has. each detector picks its best frame, and we trust
+    // the detector whose best frame stands out the most from the rest
+    let (g, _, _) = extended_gcd(room.width as i128, room.height as i128);
+    let period = room.width / g as i64 * room.height;
+    let mut scores = vec![vec![]; Detector::ALL.len()];
+    for _ in 0..period {
+        let grid = occupancy(&robots, room);
+        for (detector, scores) in Detector::ALL.iter().zip(scores.iter_mut()) {
+            scores.push(detector.score(&grid, robots.len()));
+        }
+        for robot in robots.iter_mut() {
+            robot.iter_by(1, room);
+        }
+    }
+
+    let mut best: Option<(f64, Detection)> = None;
+    for (&detector, scores) in Detector::ALL.iter().zip(scores) {
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let deviation = (scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let max = scores.iter().copied().fold(f64::MIN, f64::max);
+        let mut winners = (0..scores.len()).filter(|&i| scores[i] == max);
+        let (Some(second), None) = (winners.next(), winners.next()) else {
+            // a tie means this detector can't tell the frames apart
+            continue;
+        };
+        if deviation == 0.0 {
+            continue;
+        }
+        let z_score = (max - mean) / deviation;
+        if best.is_none_or(|(best_z, _)| z_score > best_z) {
+            let second = second as i64;
+            best = Some((z_score, Detection { second, detector }));
+        }
+    }
+    best.map(|(_, detection)| detection)
+}
+
+pub fn find_tree(input: String, params: &Params) -> Option<Detection> {
+    let room = Room::new(params);
+    detect_tree(parse_input(input, room), room)
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    let room = Room::new(params);
+    let robots = parse_input(input, room);
+
+    find_alignment(robots, room)
+        .expect("the robots never group along both axes at the same time")
+        .to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solvers::util::XorShift;
 
-    #[test]
-    fn sample_p1() {
-        let input = "p=0,4 v=3,-3
+    const SAMPLE: &str = "p=0,4 v=3,-3
 p=6,3 v=-1,-3
 p=10,3 v=-1,2
 p=2,0 v=2,-1
@@ -180,9 +484,12 @@ p=3,0 v=-1,-2
 p=9,3 v=2,3
 p=7,3 v=-1,2
 p=2,4 v=2,-3
-p=9,5 v=-3,-3"
-            .to_string();
-        assert_eq!(part1(input), "21");
+p=9,5 v=-3,-3";
+
+    #[test]
+    fn sample_p1() {
+        let input = SAMPLE.to_string();
+        assert_eq!(part1(input, &Params::default()), "21");
 
         let input = "p=38,34 v=-65,29
 p=8,22 v=-79,-66
@@ -215,11 +522,212 @@ p=38,30 v=-83,82
 p=9,19 v=-4,-32
 p=34,61 v=-11,65"
             .to_string();
-        assert_eq!(part1(input), "2640");
+        assert_eq!(part1(input, &Params::default()), "2640");
+    }
+
+    #[test]
+    fn sample_p1_small_room() {
+        let input = SAMPLE.to_string();
+        let room = Room {
+            width: 11,
+            height: 7,
+        };
+        assert_eq!(safety_factor(input.clone(), room), 12);
+
+        let mut params = Params::default();
+        params.set("width", "11");
+        params.set("height", "7");
+        assert_eq!(part1(input, &params), "12");
+    }
+
+    #[test]
+    fn robots_outside_the_room() {
+        // the sample's robots in a room smaller than the one they're from
+        let mut params = Params::default();
+        params.set("width", "5");
+        params.set("height", "4");
+        let room = Room::new(&params);
+        let robots = parse_input(SAMPLE.to_string(), room);
+        assert!(robots.iter().all(|robot| robot.x < 5 && robot.y < 4));
+        assert_eq!(robots[0].x, 0);
+        assert_eq!(robots[0].y, 0);
+        find_tree(SAMPLE.to_string(), &params);
+    }
+
+    #[test]
+    #[should_panic(expected = "the room has to be at least 1 by 1, not 0 by 7")]
+    fn empty_room() {
+        let mut params = Params::default();
+        params.set("width", "0");
+        params.set("height", "7");
+        part1(SAMPLE.to_string(), &params);
     }
 
     #[test]
     fn sample_p2() {
         // not practically testable
     }
+
+    #[test]
+    fn rendering() {
+        let input = SAMPLE.to_string();
+        let room = Room {
+            width: 11,
+            height: 7,
+        };
+        let mut robots = parse_input(input, room);
+        assert_eq!(
+            render_ascii(&robots, room, false),
+            "1.12.......
+...........
+...........
+......11.11
+1.1........
+.........1.
+.......1...
+"
+        );
+
+        for robot in robots.iter_mut() {
+            robot.iter_by(100, room);
+        }
+        assert_eq!(
+            render_ascii(&robots, room, false),
+            "......2..1.
+...........
+1..........
+.11........
+.....1.....
+...12......
+.1....1....
+"
+        );
+        assert_eq!(
+            render_ascii(&robots, room, true),
+            "..... 2..1.
+..... .....
+1.... .....
+           
+..... .....
+...12 .....
+.1... 1....
+"
+        );
+
+        let pgm = render_image(&robots, room, FrameFormat::Pgm, false);
+        assert!(pgm.starts_with(b"P5\n11 7\n255\n"));
+        assert_eq!(pgm.len(), 12 + 11 * 7);
+        assert_eq!(pgm.iter().skip(12).filter(|&&it| it == 255).count(), 10);
+
+        let ppm = render_image(&robots, room, FrameFormat::Ppm, true);
+        assert!(ppm.starts_with(b"P6\n11 7\n255\n"));
+        assert_eq!(ppm.len(), 12 + 11 * 7 * 3);
+        assert_eq!(ppm[12..15], [96, 32, 32]);
+    }
+
+    #[test]
+    fn chinese_remainder() {
+        assert_eq!(crt(2, 3, 3, 5), Some((8, 15)));
+        assert_eq!(crt(0, 101, 0, 103), Some((0, 10403)));
+        // not coprime, but the remainders agree modulo the gcd
+        assert_eq!(crt(3, 30, 18, 45), Some((63, 90)));
+        assert_eq!(crt(3, 30, 5, 45), None);
+    }
+
+    fn grouped_robots(room: Room, x_time: i64, y_time: i64) -> Vec<Robot> {
+        // robots that gather in a small square on the x axis at x_time and on
+        // the y axis at y_time, and are spread out otherwise
+        let mut rng = XorShift::new(2024);
+        let mut random = |n: i64| rng.below(n as u64) as i64;
+        (0..500)
+            .map(|_| {
+                let v_x = random(room.width * 2) - room.width;
+                let v_y = random(room.height * 2) - room.height;
+                let x = room.width / 3 + random(5) - v_x * x_time;
+                let y = room.height / 3 + random(5) - v_y * y_time;
+                Robot {
+                    x: x.rem_euclid(room.width),
+                    y: y.rem_euclid(room.height),
+                    v_x,
+                    v_y,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn alignment() {
+        let room = Room::new(&Params::default());
+        let robots = grouped_robots(room, 7520 % 101, 7520 % 103);
+        assert_eq!(find_alignment(robots, room), Some(7520));
+
+        let room = Room {
+            width: 30,
+            height: 45,
+        };
+        let robots = grouped_robots(room, 63 % 30, 63 % 45);
+        assert_eq!(find_alignment(robots, room), Some(63));
+        let robots = grouped_robots(room, 3, 5);
+        assert_eq!(find_alignment(robots, room), None);
+    }
+
+    fn robots_drawing_rectangle(room: Room, second: i64) -> Vec<Robot> {
+        // a filled rectangle with every robot on its own tile at the given
+        // second, along with some noise, which is roughly what the tree is like
+        let mut rng = XorShift::new(1224);
+        let mut random = |n: i64| rng.below(n as u64) as i64;
+        let mut tiles = (0..8)
+            .flat_map(|y| (0..10).map(move |x| (x + 12, y + 20)))
+            .collect::<Vec<_>>();
+        while tiles.len() < 150 {
+            let tile = (random(room.width), random(room.height));
+            if !tiles.contains(&tile) {
+                tiles.push(tile);
+            }
+        }
+        tiles
+            .into_iter()
+            .map(|(x, y)| {
+                let v_x = random(room.width * 2) - room.width;
+                let v_y = random(room.height * 2) - room.height;
+                Robot {
+                    x: (x - v_x * second).rem_euclid(room.width),
+                    y: (y - v_y * second).rem_euclid(room.height),
+                    v_x,
+                    v_y,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tree_detection() {
+        let room = Room {
+            width: 31,
+            height: 37,
+        };
+        let robots = robots_drawing_rectangle(room, 700);
+        let detection = detect_tree(robots, room).unwrap();
+        assert_eq!(detection.second, 700);
+
+        // every detector should find the structure on its own
+        let mut robots = robots_drawing_rectangle(room, 700);
+        for robot in robots.iter_mut() {
+            robot.iter_by(700, room);
+        }
+        let grid = occupancy(&robots, room);
+        assert!(largest_component(&grid) >= 80);
+        for detector in Detector::ALL {
+            let mut others = robots_drawing_rectangle(room, 700);
+            for robot in others.iter_mut() {
+                robot.iter_by(123, room);
+            }
+            let other_grid = occupancy(&others, room);
+            assert!(detector.score(&grid, 150) > detector.score(&other_grid, 150));
+        }
+
+        // and it should agree with the variance method
+        let robots = robots_drawing_rectangle(room, 700);
+        assert_eq!(find_alignment(robots, room), Some(700));
+    }
 }
@@ -1,244 +1,207 @@
 use super::util::Direction;
 
-#[derive(Clone, Copy)]
-enum AreaElement {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AreaElement {
     Wall,
-    Box,
-    Box2,
+    // part of a box, at this offset from its left edge
+    Box(usize),
     Empty,
 }
 
-fn try_move_box(
-    matrix: &mut Vec<Vec<AreaElement>>,
-    coord: (usize, usize),
-    direction: &Direction,
-    moving_box: bool,
-    // return true -> bot moves
-    // return false -> bot doesn't move (hits wall)
-) -> bool {
-    let front = direction.apply_unchecked(coord);
-    match matrix[front.0][front.1] {
-        AreaElement::Wall => {
-            return false;
-        }
-        AreaElement::Empty => {
-            if moving_box {
-                matrix[front.0][front.1] = AreaElement::Box
-            }
-            return true;
-        }
-        AreaElement::Box => {
-            if try_move_box(matrix, front, direction, true) {
-                if !moving_box {
-                    matrix[front.0][front.1] = AreaElement::Empty;
-                }
-                true
-            } else {
-                // bot encountered a wall so nothing moves
-                false
-            }
-        }
-        _ => unreachable!(),
-    }
-}
 fn gps_coord(coord: (usize, usize)) -> usize {
     100 * coord.0 + coord.1
 }
-pub fn part1(input: String) -> String {
-    let (area_text, commands_text) = input.split_once("\n\n").unwrap();
-    let mut robot_coord = (0, 0);
-    let mut area = area_text
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(x, c)| match c {
-                    '#' => AreaElement::Wall,
-                    'O' => AreaElement::Box,
-                    '.' => AreaElement::Empty,
-                    '@' => {
-                        robot_coord = (y, x);
-                        AreaElement::Empty
-                    }
-                    _ => unreachable!(),
-                })
-                .collect()
-        })
-        .collect::<Vec<Vec<_>>>();
-
-    let commands = commands_text.chars().filter_map(|c| match c {
-        '^' => Some(Direction::Up),
-        'v' => Some(Direction::Down),
-        '<' => Some(Direction::Left),
-        '>' => Some(Direction::Right),
-        '\n' => None,
-        _ => unreachable!("malformed input"),
-    });
+/// The warehouse as it is after each command of the robot
+pub struct Warehouse {
+    pub area: Vec<Vec<AreaElement>>,
+    // row and column of the robot
+    pub robot: (usize, usize),
+    // how many cells wide boxes are, as the area gets widened by this
+    pub box_width: usize,
+    // the command the robot was last given, whether it could move or not
+    pub last_move: Option<Direction>,
+}
 
-    for command in commands.into_iter() {
-        if try_move_box(&mut area, robot_coord, &command, false) {
-            robot_coord = command.apply_unchecked(robot_coord);
+impl Warehouse {
+    fn parse(area_text: &str, box_width: usize) -> Self {
+        assert!(box_width >= 1, "boxes have to be at least one cell wide");
+        let mut robot = (0, 0);
+        let area = area_text
+            .lines()
+            .enumerate()
+            .map(|(y, line)| {
+                let mut row = vec![];
+                line.chars().enumerate().for_each(|(x, c)| {
+                    for offset in 0..box_width {
+                        row.push(match c {
+                            '#' => AreaElement::Wall,
+                            'O' => AreaElement::Box(offset),
+                            '.' => AreaElement::Empty,
+                            '@' => {
+                                robot = (y, box_width * x);
+                                AreaElement::Empty
+                            }
+                            _ => unreachable!(),
+                        });
+                    }
+                });
+                row
+            })
+            .collect::<Vec<Vec<_>>>();
+        Warehouse {
+            area,
+            robot,
+            box_width,
+            last_move: None,
         }
     }
 
-    // for line in area.iter() {
-    //     println!(
-    //         "{:?}",
-    //         line.iter()
-    //             .map(|it| match it {
-    //                 AreaElement::Wall => '#',
-    //                 AreaElement::Empty => '.',
-    //                 AreaElement::Box => 'O',
-    //             })
-    //             .join("")
-    //     )
-    // }
-
-    area.iter()
-        .enumerate()
-        .map(|(y, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(x, elem)| match elem {
-                    AreaElement::Box => gps_coord((y, x)),
-                    _ => 0,
-                })
-                .sum::<usize>()
-        })
-        .sum::<usize>()
-        .to_string()
-}
+    /// The area with a heading like the frames of the puzzle's examples
+    pub fn frame(&self) -> String {
+        let heading = match self.last_move {
+            None => "Initial state".to_string(),
+            Some(direction) => format!("Move {}", direction.arrow()),
+        };
+        format!("{heading}:\n{}", self.render())
+    }
 
-fn move_p2(
-    area: &mut Vec<Vec<AreaElement>>,
-    direction: &Direction,
-    coord: (usize, usize),
-    // up/down moves require two passes as otherwise
-    // we might only move half the boxes
-    check_only: bool,
-) -> bool {
-    match area[coord.0][coord.1] {
-        AreaElement::Empty => true,
-        AreaElement::Wall => false,
-        AreaElement::Box => {
-            let front = direction.apply_unchecked(coord);
-            let res = move_p2(area, direction, front, check_only)
-                && move_p2(area, direction, (front.0, front.1 + 1), check_only);
-            if res && !check_only {
-                area[coord.0][coord.1] = AreaElement::Empty;
-                area[coord.0][coord.1 + 1] = AreaElement::Empty;
-                area[front.0][front.1] = AreaElement::Box;
-                area[front.0][front.1 + 1] = AreaElement::Box2;
-                return true;
+    fn push(&mut self, direction: Direction) -> bool {
+        // find every box that would have to move along with the robot, as
+        // boxes can push multiple other boxes when they are offset from each
+        // other. if any of them would hit a wall, nothing moves
+        let mut boxes: Vec<(usize, usize)> = vec![];
+        let mut to_check = vec![direction.apply_unchecked(self.robot)];
+        while let Some((y, x)) = to_check.pop() {
+            match self.area[y][x] {
+                AreaElement::Wall => return false,
+                AreaElement::Empty => {}
+                AreaElement::Box(offset) => {
+                    let left = (y, x - offset);
+                    if boxes.contains(&left) {
+                        continue;
+                    }
+                    boxes.push(left);
+                    let cells = left.1..left.1 + self.box_width;
+                    for x in cells.clone() {
+                        let front = direction.apply_unchecked((left.0, x));
+                        // the box can't block itself when moving sideways
+                        if front.0 != left.0 || !cells.contains(&front.1) {
+                            to_check.push(front);
+                        }
+                    }
+                }
             }
-            res
         }
-        AreaElement::Box2 => {
-            let front = direction.apply_unchecked(coord);
-            let res = move_p2(area, direction, front, check_only)
-                && move_p2(area, direction, (front.0, front.1 - 1), check_only);
-            if res && !check_only {
-                area[coord.0][coord.1 - 1] = AreaElement::Empty;
-                area[coord.0][coord.1] = AreaElement::Empty;
-                area[front.0][front.1 - 1] = AreaElement::Box;
-                area[front.0][front.1] = AreaElement::Box2;
-                return true;
+
+        // boxes are removed before placing them back so that they don't
+        // overwrite each other
+        for &(y, x) in boxes.iter() {
+            for cell in self.area[y][x..x + self.box_width].iter_mut() {
+                *cell = AreaElement::Empty;
+            }
+        }
+        for &left in boxes.iter() {
+            let (y, x) = direction.apply_unchecked(left);
+            for offset in 0..self.box_width {
+                self.area[y][x + offset] = AreaElement::Box(offset);
             }
-            res
         }
+        true
     }
-}
 
-fn move_lr(area: &mut Vec<Vec<AreaElement>>, direction: &Direction, coord: (usize, usize)) -> bool {
-    match area[coord.0][coord.1] {
-        AreaElement::Empty => true,
-        AreaElement::Wall => false,
-        AreaElement::Box => {
-            let next = direction.apply_unchecked(coord);
-            if move_lr(area, direction, next) {
-                area[coord.0][coord.1] = AreaElement::Empty;
-                area[next.0][next.1] = AreaElement::Box;
-                return true;
-            }
-            false
+    /// Carries out one command, returning whether the robot moved
+    pub fn step(&mut self, direction: Direction) -> bool {
+        let next_spot = direction.apply_unchecked(self.robot);
+        let moved = self.push(direction);
+        if moved {
+            self.robot = next_spot;
         }
-        AreaElement::Box2 => {
-            let next = direction.apply_unchecked(coord);
-            if move_lr(area, direction, next) {
-                area[coord.0][coord.1] = AreaElement::Empty;
-                area[next.0][next.1] = AreaElement::Box2;
-                return true;
+        self.last_move = Some(direction);
+        moved
+    }
+
+    pub fn render(&self) -> String {
+        // the same notation as the puzzle uses
+        let mut out = String::new();
+        for (y, row) in self.area.iter().enumerate() {
+            for (x, elem) in row.iter().enumerate() {
+                out.push(match *elem {
+                    _ if (y, x) == self.robot => '@',
+                    AreaElement::Wall => '#',
+                    AreaElement::Empty => '.',
+                    AreaElement::Box(_) if self.box_width == 1 => 'O',
+                    AreaElement::Box(0) => '[',
+                    AreaElement::Box(offset) if offset == self.box_width - 1 => ']',
+                    AreaElement::Box(_) => '-',
+                });
             }
-            false
+            out.push('\n');
         }
+        out
+    }
+
+    fn gps_sum(&self) -> usize {
+        self.area
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, elem)| match elem {
+                        AreaElement::Box(0) => gps_coord((y, x)),
+                        _ => 0,
+                    })
+                    .sum::<usize>()
+            })
+            .sum::<usize>()
     }
 }
 
-pub fn part2(input: String) -> String {
-    let (area_text, commands_text) = input.split_once("\n\n").unwrap();
-    let mut robot_coord = (0, 0);
-    let mut area = area_text
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            let mut row = vec![];
-            line.chars().enumerate().for_each(|(x, c)| {
-                let elem = match c {
-                    '#' => [AreaElement::Wall; 2],
-                    'O' => [AreaElement::Box, AreaElement::Box2],
-                    '.' => [AreaElement::Empty; 2],
-                    '@' => {
-                        robot_coord = (y, 2 * x);
-                        [AreaElement::Empty; 2]
-                    }
-                    _ => unreachable!(),
-                };
-                row.extend_from_slice(&elem);
-            });
-            row
-        })
-        .collect::<Vec<Vec<_>>>();
-
-    let commands = commands_text.chars().filter_map(|c| match c {
+fn parse_commands(commands_text: &str) -> impl Iterator<Item = Direction> + '_ {
+    commands_text.chars().filter_map(|c| match c {
         '^' => Some(Direction::Up),
         'v' => Some(Direction::Down),
         '<' => Some(Direction::Left),
         '>' => Some(Direction::Right),
         '\n' => None,
         _ => unreachable!("malformed input"),
-    });
-
-    for direction in commands {
-        let next_spot = direction.apply_unchecked(robot_coord);
-        let moved = match direction {
-            Direction::Down | Direction::Up => {
-                if move_p2(&mut area, &direction, next_spot, true) {
-                    move_p2(&mut area, &direction, next_spot, false)
-                } else {
-                    false
-                }
-            }
-            Direction::Left | Direction::Right => move_lr(&mut area, &direction, next_spot),
-        };
-        if moved {
-            robot_coord = next_spot;
-        }
+    })
+}
+
+/// The warehouse, widened so that boxes are `box_width` cells wide, and the
+/// commands for its robot
+pub fn parse_input(input: &str, box_width: usize) -> (Warehouse, Vec<Direction>) {
+    let (area_text, commands_text) = input.split_once("\n\n").unwrap();
+    let warehouse = Warehouse::parse(area_text, box_width);
+    (warehouse, parse_commands(commands_text).collect())
+}
+
+fn simulate(input: &str, box_width: usize) -> Warehouse {
+    let (mut warehouse, commands) = parse_input(input, box_width);
+    for command in commands {
+        warehouse.step(command);
     }
+    warehouse
+}
 
-    area.iter()
-        .enumerate()
-        .map(|(y, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(x, elem)| match elem {
-                    AreaElement::Box => gps_coord((y, x)),
-                    _ => 0,
-                })
-                .sum::<usize>()
-        })
-        .sum::<usize>()
-        .to_string()
+pub fn replay(input: String, box_width: usize) -> String {
+    // every frame in the same format as the worked examples of the puzzle
+    let (mut warehouse, commands) = parse_input(&input, box_width);
+    let mut frames = vec![warehouse.frame()];
+    for command in commands {
+        warehouse.step(command);
+        frames.push(warehouse.frame());
+    }
+    frames.join("\n")
+}
+
+pub fn part1(input: String) -> String {
+    simulate(&input, 1).gps_sum().to_string()
+}
+
+pub fn part2(input: String) -> String {
+    // the same as part 1, except everything is twice as wide
+    simulate(&input, 2).gps_sum().to_string()
 }
 
 #[cfg(test)]
@@ -285,6 +248,73 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"
         assert_eq!(part1(input), "10092");
     }
 
+    #[test]
+    fn replay_p1() {
+        let input = "########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<"
+            .to_string();
+        let frames = replay(input, 1);
+        let frames = frames.split("\n\n").collect::<Vec<_>>();
+        assert_eq!(frames.len(), 16);
+        assert_eq!(
+            frames[0],
+            "Initial state:
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########"
+        );
+        assert_eq!(
+            frames[4],
+            "Move >:
+########
+#..@OO.#
+##..O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########"
+        );
+        assert_eq!(
+            frames[7],
+            "Move v:
+########
+#....OO#
+##..@..#
+#...O..#
+#.#.O..#
+#...O..#
+#...O..#
+########"
+        );
+        assert_eq!(
+            frames[15],
+            "Move <:
+########
+#....OO#
+##.....#
+#.....O#
+#.#O@..#
+#...O..#
+#...O..#
+########
+"
+        );
+    }
+
     #[test]
     fn sample_p2() {
         let input = "#######
@@ -297,7 +327,57 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"
 
 <vv<<^^<<^^"
             .to_string();
-        assert_eq!(part2(input), "618");
+        assert_eq!(part2(input.clone()), "618");
+
+        let frames = replay(input, 2);
+        let frames = frames.split("\n\n").collect::<Vec<_>>();
+        assert_eq!(frames.len(), 12);
+        assert_eq!(
+            frames[0],
+            "Initial state:
+##############
+##......##..##
+##..........##
+##....[][]@.##
+##....[]....##
+##..........##
+##############"
+        );
+        assert_eq!(
+            frames[1],
+            "Move <:
+##############
+##......##..##
+##..........##
+##...[][]@..##
+##....[]....##
+##..........##
+##############"
+        );
+        assert_eq!(
+            frames[6],
+            "Move ^:
+##############
+##......##..##
+##...[][]...##
+##....[]....##
+##.....@....##
+##..........##
+##############"
+        );
+        assert_eq!(frames[6][8..], frames[7][8..]);
+        assert_eq!(
+            frames[11],
+            "Move ^:
+##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+"
+        );
 
         let input = "##########
 #..O..O.O#
@@ -344,4 +424,93 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"
             .to_string();
         assert_eq!(part2(input), "509");
     }
+
+    #[test]
+    fn wider_boxes() {
+        let input = "#######
+#.....#
+#.OO@.#
+#.....#
+#######
+
+<<";
+        assert_eq!(simulate(input, 3).gps_sum(), 411);
+
+        // boxes that end up offset from each other push each other vertically
+        let input = "#######
+#.....#
+#..O..#
+#..O@.#
+#.....#
+#######
+
+<v<<<^";
+        let warehouse = simulate(input, 3);
+        assert_eq!(warehouse.gps_sum(), 317);
+        assert_eq!(
+            warehouse.render(),
+            "#####################
+###......[-]......###
+###.....[-].......###
+###.....@.........###
+###...............###
+#####################
+"
+        );
+
+        // and nothing moves when any of them is blocked
+        let input = "#######
+#..#..#
+#..O..#
+#..O@.#
+#.....#
+#######
+
+<v<<<^";
+        let warehouse = simulate(input, 4);
+        assert_eq!(warehouse.robot, (4, 12));
+        assert_eq!(warehouse.gps_sum(), 212 + 311);
+    }
+
+    #[test]
+    fn stepping() {
+        let input = "########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>";
+        let (mut warehouse, commands) = parse_input(input, 1);
+        assert_eq!((warehouse.robot, warehouse.last_move), ((2, 2), None));
+
+        // a wall to the left, so the robot stays put
+        assert!(!warehouse.step(commands[0]));
+        assert_eq!(
+            (warehouse.robot, warehouse.last_move),
+            ((2, 2), Some(Direction::Left))
+        );
+        assert!(warehouse.step(commands[1]));
+        assert_eq!(warehouse.robot, (1, 2));
+        assert!(!warehouse.step(commands[2]));
+        assert_eq!(warehouse.robot, (1, 2));
+
+        // then it pushes the box along
+        assert!(warehouse.step(commands[3]));
+        assert_eq!(warehouse.robot, (1, 3));
+        assert_eq!(warehouse.area[1][3], AreaElement::Empty);
+        assert_eq!(warehouse.area[1][4], AreaElement::Box(0));
+        assert!(warehouse
+            .frame()
+            .starts_with("Move >:\n########\n#..@OO.#\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "boxes have to be at least one cell wide")]
+    fn zero_width_boxes() {
+        replay("#@#\n\n<".to_string(), 0);
+    }
 }
@@ -1,8 +1,32 @@
-use std::collections::BinaryHeap;
-
-use rustc_hash::{FxHashMap, FxHashSet};
-
-use super::util::Direction;
+use std::{collections::BinaryHeap, fmt};
+
+use num_bigint::BigUint;
+use rustc_hash::FxHashSet;
+
+use super::{
+    params::{parses, Param, Params},
+    util::Direction,
+};
+
+const STEP_COST: Param = Param {
+    key: "step_cost",
+    default: "1",
+    help: "points for a step forward",
+    parse: parses::<u64>,
+};
+const TURN_COST: Param = Param {
+    key: "turn_cost",
+    default: "1000",
+    help: "points for turning 90 degrees",
+    parse: parses::<u64>,
+};
+const FACING: Param = Param {
+    key: "facing",
+    default: "east",
+    help: "which way the reindeer starts out facing",
+    parse: parses::<Direction>,
+};
+pub const PARAMS: &[Param] = &[STEP_COST, TURN_COST, FACING];
 
 #[derive(PartialEq)]
 enum Element {
@@ -15,8 +39,6 @@ struct Dijk {
     pos: (usize, usize),
     cost: u64,
     dir: Direction,
-    // for p2. probably minimal cost to have it as None for p1
-    path: Option<Vec<(usize, usize)>>,
 }
 
 impl Ord for Dijk {
@@ -31,87 +53,188 @@ impl PartialOrd for Dijk {
     }
 }
 
-fn turns(direction: &Direction) -> impl Iterator<Item = (Direction, u64)> {
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    step: u64,
+    turn: u64,
+    facing: Direction,
+}
+
+impl Rules {
+    fn new(params: &Params) -> Rules {
+        let step = params.get(&STEP_COST);
+        // with free steps every tile costs as much as the start to reach, and
+        // there are endless optimal routes through them
+        assert!(step > 0, "the step cost has to be at least 1");
+        Rules {
+            step,
+            turn: params.get(&TURN_COST),
+            facing: params.get(&FACING),
+        }
+    }
+}
+
+fn turns(direction: &Direction, rules: Rules) -> impl Iterator<Item = (Direction, u64)> {
+    // there's no point in standing still so we automatically add the cost of
+    // a step onto a turn
+    let (straight, turn) = (rules.step, rules.turn + rules.step);
     match direction {
         Direction::Down => [
-            (Direction::Down, 0),
-            (Direction::Right, 1000),
-            (Direction::Left, 1000),
+            (Direction::Down, straight),
+            (Direction::Right, turn),
+            (Direction::Left, turn),
         ]
         .into_iter(),
         Direction::Up => [
-            (Direction::Up, 0),
-            (Direction::Right, 1000),
-            (Direction::Left, 1000),
+            (Direction::Up, straight),
+            (Direction::Right, turn),
+            (Direction::Left, turn),
         ]
         .into_iter(),
         Direction::Left => [
-            (Direction::Left, 0),
-            (Direction::Up, 1000),
-            (Direction::Down, 1000),
+            (Direction::Left, straight),
+            (Direction::Up, turn),
+            (Direction::Down, turn),
         ]
         .into_iter(),
         Direction::Right => [
-            (Direction::Right, 0),
-            (Direction::Up, 1000),
-            (Direction::Down, 1000),
+            (Direction::Right, straight),
+            (Direction::Up, turn),
+            (Direction::Down, turn),
         ]
         .into_iter(),
     }
 }
 
-fn find_path(map: &Vec<Vec<Element>>, source: (usize, usize), target: (usize, usize)) -> u64 {
-    // dijkstra's algorithm, but instead of storing costs per 2d index, we want to store
-    // them per (2d index, direction) as turning is treated separately from moving
-    let mut costs = FxHashMap::default();
-    let mut heap = BinaryHeap::new();
+#[derive(Debug, PartialEq)]
+struct NoRoute;
 
-    heap.push(Dijk {
-        pos: source,
-        cost: 0,
-        dir: Direction::Right,
-        path: None,
-    });
+impl fmt::Display for NoRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "there is no route from the start to the end")
+    }
+}
 
-    while !heap.is_empty() {
-        let u = heap.pop().unwrap();
+#[derive(Debug)]
+pub struct Route {
+    pub cost: u64,
+    // every tile along the route, along with the facing the reindeer had when
+    // it arrived there. the start has the initial facing
+    pub steps: Vec<((usize, usize), Direction)>,
+}
 
-        if u.pos == target {
-            return u.cost;
-        }
+impl Route {
+    pub fn turns(&self) -> Vec<((usize, usize), Direction, Direction)> {
+        // the tiles where the reindeer turned, and from which facing to which
+        self.steps
+            .windows(2)
+            .filter(|pair| pair[0].1 != pair[1].1)
+            .map(|pair| (pair[0].0, pair[0].1, pair[1].1))
+            .collect()
+    }
+}
+
+fn state(width: usize, (y, x): (usize, usize), dir: Direction) -> usize {
+    // index into the dense cost arrays, with a slot per direction for each tile
+    (y * width + x) * 4 + dir as usize
+}
+
+fn dijkstra(
+    map: &[Vec<Element>],
+    sources: &[((usize, usize), Direction)],
+    reverse: bool,
+    rules: Rules,
+) -> Vec<u64> {
+    // dijkstra's algorithm, but instead of storing costs per 2d index, we want to store
+    // them per (2d index, direction) as turning is treated separately from moving.
+    // in reverse we instead get the cost from every state to reach any of the sources
+    let width = map[0].len();
+    let mut costs = vec![u64::MAX; map.len() * width * 4];
+    let mut heap = BinaryHeap::new();
 
-        if costs.get(&(u.pos, u.dir)).is_some_and(|&it| it <= u.cost) {
+    for &(pos, dir) in sources {
+        heap.push(Dijk { pos, cost: 0, dir });
+    }
+
+    while let Some(u) = heap.pop() {
+        let idx = state(width, u.pos, u.dir);
+        if costs[idx] <= u.cost {
             continue;
         }
-
-        costs.insert((u.pos, u.dir), u.cost);
+        costs[idx] = u.cost;
 
         // at each position we might want to turn to a shorter path
-        // instead of going forward
-        for (new_dir, turn_cost) in turns(&u.dir) {
-            let new_pos = new_dir.apply_unchecked(u.pos);
+        // instead of going forward. in reverse, we step back first and then
+        // undo the turn that was made there
+        for (new_dir, move_cost) in turns(&u.dir, rules) {
+            let new_pos = if reverse {
+                u.dir.opposite().apply_unchecked(u.pos)
+            } else {
+                new_dir.apply_unchecked(u.pos)
+            };
 
             // let's not walk into a wall
             if map[new_pos.0][new_pos.1] != Element::Empty {
                 continue;
             }
 
-            // there's no point in standing still so we automatically
-            // add 1001 instead of 1000 on a turn
-            let new_cost = u.cost + turn_cost + 1;
+            let new_cost = u.cost + move_cost;
+            if costs[state(width, new_pos, new_dir)] <= new_cost {
+                continue;
+            }
 
             heap.push(Dijk {
                 pos: new_pos,
                 cost: new_cost,
                 dir: new_dir,
-                path: None,
             });
         }
     }
 
-    u64::MAX
+    costs
 }
-pub fn part1(input: String) -> String {
+
+fn find_route(
+    map: &[Vec<Element>],
+    source: (usize, usize),
+    target: (usize, usize),
+    rules: Rules,
+) -> Result<Route, NoRoute> {
+    let width = map[0].len();
+    let forward = dijkstra(map, &[(source, rules.facing)], false, rules);
+    let (cost, mut dir) = Direction::ALL
+        .into_iter()
+        .map(|dir| (forward[state(width, target, dir)], dir))
+        .min_by_key(|&(cost, _)| cost)
+        .unwrap();
+    if cost == u64::MAX {
+        return Err(NoRoute);
+    }
+
+    // walk back from the target through states whose cost explains the
+    // cost of the state after them, until we get to the start
+    let mut pos = target;
+    let mut steps = vec![(pos, dir)];
+    while forward[state(width, pos, dir)] != 0 {
+        let current = forward[state(width, pos, dir)];
+        let previous_pos = dir.opposite().apply_unchecked(pos);
+        (dir, _) = turns(&dir, rules)
+            .find(|&(previous_dir, move_cost)| {
+                let previous = forward[state(width, previous_pos, previous_dir)];
+                previous.checked_add(move_cost) == Some(current)
+            })
+            .unwrap();
+        pos = previous_pos;
+        steps.push((pos, dir));
+    }
+    steps.reverse();
+
+    Ok(Route { cost, steps })
+}
+
+type Maze = Vec<Vec<Element>>;
+
+fn parse_input(input: &str) -> (Maze, (usize, usize), (usize, usize)) {
     let mut start = (0, 0);
     let mut end = (0, 0);
     let map = input
@@ -139,125 +262,196 @@ pub fn part1(input: String) -> String {
 
     assert_ne!(start, (0, 0));
     assert_ne!(end, (0, 0));
+    (map, start, end)
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    let (map, start, end) = parse_input(&input);
 
-    find_path(&map, start, end).to_string()
+    find_route(&map, start, end, Rules::new(params))
+        .unwrap_or_else(|e| panic!("{e}"))
+        .cost
+        .to_string()
 }
 
-fn p2_paths(
-    map: &Vec<Vec<Element>>,
+fn costs_both_ways(
+    map: &[Vec<Element>],
     source: (usize, usize),
     target: (usize, usize),
-) -> FxHashSet<(usize, usize)> {
-    // similar to p1, but does not terminate as early due to requiring all shortest paths
-    // and dijk is modified to contain a vector of the path
-    // which is cloned *a lot*
-    // TODO: this could probably be optimised quite a bit by backtracking from the target
-    // instead of keeping countless path vectors that get discarded when the pathfinding goes the wrong way
-    let mut costs = FxHashMap::default();
-    let mut heap = BinaryHeap::new();
+    rules: Rules,
+) -> Result<(Vec<u64>, Vec<u64>, u64), NoRoute> {
+    // a state is on an optimal path exactly when the cost to get there from
+    // the start and the cost to get from there to the end add up to the
+    // optimal cost, so we only need one search from each side
+    let width = map[0].len();
+    let forward = dijkstra(map, &[(source, rules.facing)], false, rules);
+    let ends = Direction::ALL.map(|dir| (target, dir));
+    let backward = dijkstra(map, &ends, true, rules);
+    let best = Direction::ALL
+        .into_iter()
+        .map(|dir| forward[state(width, target, dir)])
+        .min()
+        .unwrap();
+    if best == u64::MAX {
+        return Err(NoRoute);
+    }
+    Ok((forward, backward, best))
+}
+
+fn optimal_tiles(
+    map: &[Vec<Element>],
+    source: (usize, usize),
+    target: (usize, usize),
+    rules: Rules,
+) -> Result<FxHashSet<(usize, usize)>, NoRoute> {
+    let width = map[0].len();
+    let (forward, backward, best) = costs_both_ways(map, source, target, rules)?;
+
     let mut tiles = FxHashSet::default();
-    let mut shortest_cost = None;
-
-    heap.push(Dijk {
-        pos: source,
-        cost: 0,
-        dir: Direction::Right,
-        path: Some(vec![source]),
-    });
-
-    while !heap.is_empty() {
-        let cur = heap.pop().unwrap();
-
-        if cur.pos == target {
-            if shortest_cost.is_none() {
-                shortest_cost.replace(cur.cost);
-            } else if shortest_cost.unwrap() < cur.cost {
-                continue;
+    for (y, row) in map.iter().enumerate() {
+        for x in 0..row.len() {
+            let on_path = Direction::ALL.into_iter().any(|dir| {
+                let idx = state(width, (y, x), dir);
+                forward[idx].checked_add(backward[idx]) == Some(best)
+            });
+            if on_path {
+                tiles.insert((y, x));
             }
-            tiles.extend(cur.path.unwrap().into_iter());
-            continue;
         }
+    }
+    Ok(tiles)
+}
 
-        // at each position we might want to turn to a shorter path
-        // instead of going forward
-        for (new_dir, turn_cost) in turns(&cur.dir) {
-            let new_pos = new_dir.apply_unchecked(cur.pos);
-
-            // let's not walk into a wall
-            if map[new_pos.0][new_pos.1] == Element::Wall {
-                continue;
+fn count_optimal_routes(
+    map: &[Vec<Element>],
+    source: (usize, usize),
+    target: (usize, usize),
+    rules: Rules,
+) -> Result<BigUint, NoRoute> {
+    // the moves between optimal states form a DAG, as every move costs at
+    // least a step. going through the states by their cost from the start, the
+    // number of routes to a state is the sum of routes to the states leading to it
+    assert!(
+        rules.step > 0,
+        "free steps would allow endless optimal routes"
+    );
+    let width = map[0].len();
+    let (forward, backward, best) = costs_both_ways(map, source, target, rules)?;
+    let is_optimal = |idx: usize| forward[idx].checked_add(backward[idx]) == Some(best);
+
+    let mut states = vec![];
+    for (y, row) in map.iter().enumerate() {
+        for x in 0..row.len() {
+            for dir in Direction::ALL {
+                if is_optimal(state(width, (y, x), dir)) {
+                    states.push(((y, x), dir));
+                }
             }
-
-            // we automatically add 1001 instead of 1000 on a turn
-            let new_cost = cur.cost + turn_cost + 1;
-
-            let new_key = (new_pos, new_dir);
-            // avoid calculating longer paths to an already as or less optimal calculated spot by skipping this move
-            let existing_cost = costs.get(&new_key);
-            if existing_cost.is_some_and(|&it| it < new_cost) {
-                continue;
+        }
+    }
+    states.sort_by_key(|&(pos, dir)| forward[state(width, pos, dir)]);
+
+    let mut routes = vec![BigUint::ZERO; forward.len()];
+    routes[state(width, source, rules.facing)] = BigUint::from(1u32);
+    let mut total = BigUint::ZERO;
+    for (pos, dir) in states {
+        let idx = state(width, pos, dir);
+        if pos == target {
+            total += &routes[idx];
+            continue;
+        }
+        for (new_dir, move_cost) in turns(&dir, rules) {
+            let new_pos = new_dir.apply_unchecked(pos);
+            let new_idx = state(width, new_pos, new_dir);
+            if map[new_pos.0][new_pos.1] == Element::Empty
+                && is_optimal(new_idx)
+                && forward[idx] + move_cost == forward[new_idx]
+            {
+                let from = routes[idx].clone();
+                routes[new_idx] += from;
             }
-
-            costs.insert(new_key, new_cost);
-
-            let mut new_path = cur.path.clone().unwrap();
-            new_path.push(new_pos);
-            heap.push(Dijk {
-                pos: new_pos,
-                cost: new_cost,
-                dir: new_dir,
-                path: Some(new_path),
-            });
         }
     }
+    Ok(total)
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    let (map, start, end) = parse_input(&input);
 
-    tiles
+    optimal_tiles(&map, start, end, Rules::new(params))
+        .unwrap_or_else(|e| panic!("{e}"))
+        .len()
+        .to_string()
 }
 
-pub fn part2(input: String) -> String {
-    let mut start = (0, 0);
-    let mut end = (0, 0);
-    let map = input
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(x, c)| match c {
-                    'S' => {
-                        start = (y, x);
-                        Element::Empty
-                    }
-                    'E' => {
-                        end = (y, x);
-                        Element::Empty
-                    }
-                    '#' => Element::Wall,
-                    '.' => Element::Empty,
-                    _ => unreachable!("malformed input"),
+fn render(
+    map: &[Vec<Element>],
+    (start, end): ((usize, usize), (usize, usize)),
+    route: Option<&Route>,
+    tiles: Option<&FxHashSet<(usize, usize)>>,
+) -> String {
+    // optimal path tiles are drawn as O like in the puzzle, and the route with
+    // arrows showing which way the reindeer left each tile
+    let mut grid = map
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|elem| match elem {
+                    Element::Wall => '#',
+                    Element::Empty => '.',
                 })
-                .collect()
+                .collect::<Vec<_>>()
         })
-        .collect::<Vec<Vec<_>>>();
-
-    assert_ne!(start, (0, 0));
-    assert_ne!(end, (0, 0));
-
-    let tiles = p2_paths(&map, start, end);
+        .collect::<Vec<_>>();
+    for &(y, x) in tiles.into_iter().flatten() {
+        grid[y][x] = 'O';
+    }
+    if let Some(route) = route {
+        for pair in route.steps.windows(2) {
+            let ((y, x), _) = pair[0];
+            grid[y][x] = pair[1].1.arrow();
+        }
+        grid[start.0][start.1] = 'S';
+        grid[end.0][end.1] = 'E';
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
+}
 
-    tiles.len().to_string()
+pub fn show_route(input: String, params: &Params) -> String {
+    let (map, start, end) = parse_input(&input);
+    let rules = Rules::new(params);
+    let route = match find_route(&map, start, end, rules) {
+        Ok(route) => route,
+        Err(e) => return e.to_string(),
+    };
+    let tiles = optimal_tiles(&map, start, end, rules).unwrap();
+    let count = count_optimal_routes(&map, start, end, rules).unwrap();
+
+    let mut out = format!("Route with cost {}:\n", route.cost);
+    out += &render(&map, (start, end), Some(&route), None);
+    for ((y, x), from, to) in route.turns() {
+        out += &format!("Turned from {from:?} to {to:?} at ({y}, {x})\n");
+    }
+    out += &format!(
+        "\nAll {} tiles on the {count} optimal routes:\n",
+        tiles.len()
+    );
+    out += &render(&map, (start, end), None, Some(&tiles));
+    out
 }
 
 #[cfg(test)]
 mod tests {
     #[test]
     fn sample_p1() {
-        use super::part1;
+        use super::{part1, Params};
         let input = "#######
 #S...E#
 #######"
             .to_string();
-        assert_eq!(part1(input), "4");
+        assert_eq!(part1(input, &Params::default()), "4");
 
         // not sure if this needs fixing. currently you can't turn
         // 180 degrees in place, but my input doesn't seem to require this?
@@ -265,14 +459,14 @@ mod tests {
         // #E...S#
         // #######"
         //             .to_string();
-        //         assert_eq!(part1(input), "2004");
+        //         assert_eq!(part1(input, &Params::default()), "2004");
 
         let input = "#######
 #S....#
 #####E#
 #######"
             .to_string();
-        assert_eq!(part1(input), "1005");
+        assert_eq!(part1(input, &Params::default()), "1005");
 
         let input = "#######
 #S....#
@@ -280,7 +474,7 @@ mod tests {
 #E....#
 #######"
             .to_string();
-        assert_eq!(part1(input), "2010");
+        assert_eq!(part1(input, &Params::default()), "2010");
 
         let input = "###############
 #.......#....E#
@@ -298,7 +492,7 @@ mod tests {
 #S..#.....#...#
 ###############"
             .to_string();
-        assert_eq!(part1(input), "7036");
+        assert_eq!(part1(input, &Params::default()), "7036");
 
         let input = "#################
 #...#...#...#..E#
@@ -318,17 +512,17 @@ mod tests {
 #S#.............#
 #################"
             .to_string();
-        assert_eq!(part1(input), "11048");
+        assert_eq!(part1(input, &Params::default()), "11048");
     }
 
     #[test]
     fn sample_p2() {
-        use super::part2;
+        use super::{part2, Params};
         let input = "#######
 #S...E#
 #######"
             .to_string();
-        assert_eq!(part2(input), "5");
+        assert_eq!(part2(input, &Params::default()), "5");
 
         let input = "#######
 #S....#
@@ -336,7 +530,7 @@ mod tests {
 #E....#
 #######"
             .to_string();
-        assert_eq!(part2(input), "11");
+        assert_eq!(part2(input, &Params::default()), "11");
 
         let input = "###############
 #.......#....E#
@@ -354,7 +548,7 @@ mod tests {
 #S..#.....#...#
 ###############"
             .to_string();
-        assert_eq!(part2(input), "45");
+        assert_eq!(part2(input, &Params::default()), "45");
 
         let input = "#################
 #...#...#...#..E#
@@ -374,6 +568,165 @@ mod tests {
 #S#.............#
 #################"
             .to_string();
-        assert_eq!(part2(input), "64");
+        assert_eq!(part2(input, &Params::default()), "64");
+    }
+
+    #[test]
+    fn routes() {
+        use super::*;
+
+        let puzzle = Rules::new(&Params::default());
+
+        let input = "###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+        let (map, start, end) = parse_input(input);
+        let route = find_route(&map, start, end, puzzle).unwrap();
+        assert_eq!(route.cost, 7036);
+        assert_eq!(route.steps.len(), 37);
+        assert_eq!(route.steps[0], (start, Direction::Right));
+        assert_eq!(route.steps[36], (end, Direction::Up));
+        // every turn costs 1000 and every step 1
+        assert_eq!(route.turns().len(), 7);
+        assert_eq!(route.turns()[0], ((13, 1), Direction::Right, Direction::Up));
+
+        let tiles = optimal_tiles(&map, start, end, puzzle).unwrap();
+        assert_eq!(
+            render(&map, (start, end), None, Some(&tiles)),
+            "###############
+#.......#....O#
+#.#.###.#.###O#
+#.....#.#...#O#
+#.###.#####.#O#
+#.#.#.......#O#
+#.#.#####.###O#
+#..OOOOOOOOO#O#
+###O#O#####O#O#
+#OOO#O....#O#O#
+#O#O#O###.#O#O#
+#OOOOO#...#O#O#
+#O###.#.#.#O#O#
+#O..#.....#OOO#
+###############
+"
+        );
+
+        let rendered = render(&map, (start, end), Some(&route), None);
+        assert_eq!(rendered.matches(['^', 'v', '<', '>']).count(), 35);
+        assert!(rendered.starts_with("###############\n#.......#....E#\n#.#.###.#.###^#\n"));
+
+        let input = "#######
+#S#...#
+###.#E#
+#######";
+        let (map, start, end) = parse_input(input);
+        assert_eq!(find_route(&map, start, end, puzzle).unwrap_err(), NoRoute);
+        assert_eq!(
+            optimal_tiles(&map, start, end, puzzle).unwrap_err(),
+            NoRoute
+        );
+    }
+
+    #[test]
+    fn route_counts() {
+        use super::*;
+
+        let puzzle = Rules::new(&Params::default());
+
+        let input = "###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+        let (map, start, end) = parse_input(input);
+        let count = count_optimal_routes(&map, start, end, puzzle).unwrap();
+        assert_eq!(count, BigUint::from(3u32));
+
+        // a square room, where turning for free allows every lattice path
+        let input = "#####
+#S..#
+#...#
+#..E#
+#####";
+        let (map, start, end) = parse_input(input);
+        let free_turns = Rules { turn: 0, ..puzzle };
+        let count = count_optimal_routes(&map, start, end, free_turns).unwrap();
+        assert_eq!(count, BigUint::from(6u32));
+        assert_eq!(find_route(&map, start, end, free_turns).unwrap().cost, 4);
+
+        // otherwise only the route that turns once is optimal, which depends on
+        // which way the reindeer starts out facing
+        let route = find_route(&map, start, end, puzzle).unwrap();
+        assert_eq!(route.turns(), [((1, 3), Direction::Right, Direction::Down)]);
+        let mut params = Params::default();
+        params.set("facing", "South");
+        let facing_down = Rules::new(&params);
+        let route = find_route(&map, start, end, facing_down).unwrap();
+        assert_eq!(route.turns(), [((3, 1), Direction::Down, Direction::Right)]);
+        let expensive_steps = Rules {
+            step: 10,
+            turn: 5,
+            facing: Direction::Up,
+        };
+        let route = find_route(&map, start, end, expensive_steps).unwrap();
+        assert_eq!(route.cost, 4 * 10 + 2 * 5);
+        assert_eq!(route.turns()[0], ((1, 1), Direction::Up, Direction::Right));
+        let count = count_optimal_routes(&map, start, end, expensive_steps).unwrap();
+        assert_eq!(count, BigUint::from(1u32));
+
+        // far more routes than fit in a u128
+        let mut rows = vec!["#".repeat(71)];
+        rows.extend((0..69).map(|_| format!("#{}#", ".".repeat(69))));
+        rows.push("#".repeat(71));
+        rows[1].replace_range(1..2, "S");
+        rows[69].replace_range(69..70, "E");
+        let (map, start, end) = parse_input(&rows.join("\n"));
+        let count = count_optimal_routes(&map, start, end, free_turns).unwrap();
+        assert_eq!(
+            count.to_string(),
+            "5949105755928259715106809205795376486500"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value up for facing: up is not north, south, east or west")]
+    fn unknown_facing() {
+        use super::*;
+
+        let mut params = Params::default();
+        params.set("facing", "up");
+        Rules::new(&params);
+    }
+
+    #[test]
+    #[should_panic(expected = "the step cost has to be at least 1")]
+    fn free_steps() {
+        use super::*;
+
+        let mut params = Params::default();
+        params.set("step_cost", "0");
+        Rules::new(&params);
     }
 }
@@ -1,6 +1,25 @@
 use std::collections::VecDeque;
 
-use super::util::{adjacent_in_bounds, get_2d};
+use clap::ValueEnum;
+
+use super::{
+    params::{parses, Param, Params},
+    util::adjacent_in_bounds,
+};
+
+const SIZE: Param = Param {
+    key: "size",
+    default: "71",
+    help: "how many tiles wide and tall the memory space is",
+    parse: parses::<usize>,
+};
+const FALLEN: Param = Param {
+    key: "fallen",
+    default: "1024",
+    help: "how many bytes have fallen before walking through in part 1",
+    parse: parses::<usize>,
+};
+pub const PARAMS: &[Param] = &[SIZE, FALLEN];
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Tile {
@@ -8,41 +27,69 @@ enum Tile {
     Wall,
 }
 
+/// The square memory space the bytes fall into, and how many of them have
+/// fallen by the time we walk through it in part 1
+#[derive(Debug, Clone, Copy)]
+struct Memory {
+    size: usize,
+    fallen: usize,
+}
+
+impl Memory {
+    fn new(params: &Params) -> Memory {
+        Memory {
+            size: params.get(&SIZE),
+            fallen: params.get(&FALLEN),
+        }
+    }
+
+    fn exit(&self) -> (usize, usize) {
+        (self.size - 1, self.size - 1)
+    }
+}
+
+type Path = Vec<(usize, usize)>;
+
 fn find_shortest_path(
-    area: &Vec<Vec<Tile>>,
+    area: &[Vec<Tile>],
     source: (usize, usize),
     target: (usize, usize),
-) -> Option<u64> {
-    // simple bfs
+) -> Option<Path> {
+    // simple bfs, remembering where we came from to walk the path back
     let mut heap = VecDeque::new();
-    let mut seen = vec![false; area.len() * area.len()];
-    seen[source.0 * area.len() + source.1] = true;
+    let mut previous = vec![None; area.len() * area.len()];
+    previous[source.0 * area.len() + source.1] = Some(source);
 
-    heap.push_back((0, source));
+    heap.push_back(source);
 
-    while let Some((current_cost, current_pos)) = heap.pop_front() {
+    while let Some(current_pos) = heap.pop_front() {
         if current_pos == target {
-            return Some(current_cost);
+            let mut path = vec![target];
+            while let Some(&pos) = path.last().filter(|&&pos| pos != source) {
+                path.push(previous[pos.0 * area.len() + pos.1].unwrap());
+            }
+            path.reverse();
+            return Some(path);
         }
 
         for (y, x) in adjacent_in_bounds(current_pos.0, current_pos.1, area.len()) {
             // let's not walk into a wall
-            if area[y][x] == Tile::Wall || seen[y * area.len() + x] {
+            if area[y][x] == Tile::Wall || previous[y * area.len() + x].is_some() {
                 continue;
             }
-            seen[y * area.len() + x] = true;
+            previous[y * area.len() + x] = Some(current_pos);
 
-            heap.push_back((current_cost + 1, (y, x)));
+            heap.push_back((y, x));
         }
     }
 
     None
 }
 
-fn has_path(area: &Vec<Vec<Tile>>, source: (usize, usize), target: (usize, usize)) -> bool {
+fn has_path(area: &[Vec<Tile>], source: (usize, usize), target: (usize, usize)) -> bool {
     // DFS.
     // [bool] seems to be slightly faster than a bitarr here
-    let mut seen = [false; 71 * 71];
+    let mut seen = vec![false; area.len() * area.len()];
     let mut stack = vec![source];
     while let Some(current) = stack.pop() {
         let seen_idx = current.0 * area.len() + current.1;
@@ -64,55 +111,264 @@ fn has_path(area: &Vec<Vec<Tile>>, source: (usize, usize), target: (usize, usize
     false
 }
 
-fn parse_input(input: String) -> (Vec<(usize, usize)>, Vec<Vec<Tile>>) {
+fn parse_input(input: &str, memory: Memory) -> (Vec<(usize, usize)>, Vec<Vec<Tile>>) {
     let mut bytes = input.lines().map(|line| {
         let (lhs, rhs) = line.split_once(',').unwrap();
         (lhs.parse::<usize>().unwrap(), rhs.parse::<usize>().unwrap())
     });
-    let mut area = vec![vec![Tile::Empty; 71]; 71];
+    let mut area = vec![vec![Tile::Empty; memory.size]; memory.size];
 
-    // p1 guarantees that the first 1024 bytes don't block p2, so we can consume
-    // the same amount for p1 and p2
-    for (y, x) in bytes.by_ref().take(1024) {
+    // p1 guarantees that the bytes that have fallen by then don't block p2, so
+    // we can consume the same amount for p1 and p2
+    for (y, x) in bytes.by_ref().take(memory.fallen) {
         area[y][x] = Tile::Wall;
     }
     (bytes.collect(), area)
 }
-pub fn part1(input: String) -> String {
-    let (_, area) = parse_input(input);
-    find_shortest_path(&area, (0, 0), (70, 70))
+
+fn shortest_path(input: &str, memory: Memory) -> Option<u64> {
+    let (_, area) = parse_input(input, memory);
+    find_shortest_path(&area, (0, 0), memory.exit()).map(|path| path.len() as u64 - 1)
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    shortest_path(&input, Memory::new(params))
         .expect("day 18 p1 error: no path found")
         .to_string()
 }
 
-fn index_is_tile_or_none(area: &Vec<Vec<Tile>>, y: usize, x: usize) -> bool {
-    get_2d(area, (y, x)).is_none_or(|tile| match tile {
-        Tile::Wall => true,
-        _ => false,
-    })
+/// How to find the first byte that cuts the exit off from the start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CutOff {
+    /// Binary search over how many bytes have fallen, checking for a path each time
+    BinarySearch,
+    /// Let every byte fall, then take them away again from the last one,
+    /// joining up the regions they separated until the start and exit meet
+    UnionFind,
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
 }
-fn blocks_any_path(area: &Vec<Vec<Tile>>, y: usize, x: usize) -> bool {
-    if (index_is_tile_or_none(area, y, x.wrapping_sub(1)) && index_is_tile_or_none(area, y, x + 1))
-        || (index_is_tile_or_none(area, y.wrapping_sub(1), x)
-            && index_is_tile_or_none(area, y + 1, x))
-    {
-        return true;
+
+impl UnionFind {
+    fn new(len: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
     }
 
-    false
+    fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            // path halving keeps the trees flat without recursion
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+fn cut_off_by_binary_search(
+    bytes: &[(usize, usize)],
+    area: &[Vec<Tile>],
+    exit: (usize, usize),
+) -> Option<(usize, usize)> {
+    if !has_path(area, (0, 0), exit) {
+        return None;
+    }
+    // the number of fallen bytes after which there's still a path
+    let open = (0..bytes.len())
+        .collect::<Vec<_>>()
+        .partition_point(|&fallen| {
+            let mut area = area.to_vec();
+            for &(y, x) in &bytes[..=fallen] {
+                area[y][x] = Tile::Wall;
+            }
+            has_path(&area, (0, 0), exit)
+        });
+    bytes.get(open).copied()
+}
+
+fn cut_off_by_union_find(
+    bytes: &[(usize, usize)],
+    area: &[Vec<Tile>],
+    exit: (usize, usize),
+) -> Option<(usize, usize)> {
+    let size = area.len();
+    let idx = |(y, x): (usize, usize)| y * size + x;
+
+    // bytes can land on the same tile more than once, so a tile only opens up
+    // again once every byte on it has been taken away
+    let mut walls = vec![0u32; size * size];
+    for (y, row) in area.iter().enumerate() {
+        for (x, &tile) in row.iter().enumerate() {
+            if tile == Tile::Wall {
+                walls[idx((y, x))] += 1;
+            }
+        }
+    }
+    for &byte in bytes {
+        walls[idx(byte)] += 1;
+    }
+
+    let mut regions = UnionFind::new(size * size);
+    let open_up = |regions: &mut UnionFind, walls: &[u32], pos: (usize, usize)| {
+        for adjacent in adjacent_in_bounds(pos.0, pos.1, size) {
+            if walls[idx(adjacent)] == 0 {
+                regions.union(idx(pos), idx(adjacent));
+            }
+        }
+    };
+    for y in 0..size {
+        for x in 0..size {
+            if walls[idx((y, x))] == 0 {
+                open_up(&mut regions, &walls, (y, x));
+            }
+        }
+    }
+
+    let connected = |regions: &mut UnionFind, walls: &[u32]| {
+        walls[idx((0, 0))] == 0 && regions.find(idx((0, 0))) == regions.find(idx(exit))
+    };
+    if connected(&mut regions, &walls) {
+        return None;
+    }
+    for &byte in bytes.iter().rev() {
+        walls[idx(byte)] -= 1;
+        if walls[idx(byte)] > 0 {
+            continue;
+        }
+        open_up(&mut regions, &walls, byte);
+        if connected(&mut regions, &walls) {
+            return Some(byte);
+        }
+    }
+    // not even the bytes that fell before the first one we looked at let us
+    // through
+    None
+}
+
+fn first_blocking_byte(input: &str, memory: Memory, strategy: CutOff) -> Option<(usize, usize)> {
+    let (remaining_bytes, area) = parse_input(input, memory);
+    match strategy {
+        CutOff::BinarySearch => cut_off_by_binary_search(&remaining_bytes, &area, memory.exit()),
+        CutOff::UnionFind => cut_off_by_union_find(&remaining_bytes, &area, memory.exit()),
+    }
+}
+
+pub fn cut_off(input: String, strategy: CutOff, params: &Params) -> String {
+    match first_blocking_byte(&input, Memory::new(params), strategy) {
+        Some((y, x)) => format!("{y},{x}"),
+        None => "-1".to_string(),
+    }
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    cut_off(input, CutOff::UnionFind, params)
+}
+
+fn render(area: &[Vec<Tile>], path: &[(usize, usize)], blocker: Option<(usize, usize)>) -> String {
+    // bytes are given as x,y in the puzzle and we store them the other way
+    // around, so we draw the area transposed to look like the puzzle's drawings
+    let mut grid = (0..area.len())
+        .map(|x| {
+            area.iter()
+                .map(|row| match row[x] {
+                    Tile::Wall => '#',
+                    Tile::Empty => '.',
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    for &(y, x) in path {
+        grid[x][y] = 'O';
+    }
+    if let Some((y, x)) = blocker {
+        grid[x][y] = 'X';
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
 }
-pub fn part2(input: String) -> String {
-    let (remaining_bytes, mut area) = parse_input(input);
-    for byte in remaining_bytes.into_iter() {
+
+fn fall(input: &str, memory: Memory) -> Vec<String> {
+    // only bytes that land on the current path change it, so those are the
+    // frames worth showing
+    let (remaining_bytes, mut area) = parse_input(input, memory);
+    let Some(mut path) = find_shortest_path(&area, (0, 0), memory.exit()) else {
+        return vec![format!(
+            "After {} bytes there is no path:\n{}",
+            memory.fallen,
+            render(&area, &[], None)
+        )];
+    };
+    let mut frames = vec![format!(
+        "After {} bytes the shortest path takes {} steps:\n{}",
+        memory.fallen,
+        path.len() - 1,
+        render(&area, &path, None)
+    )];
+    for (n, byte) in remaining_bytes.into_iter().enumerate() {
         area[byte.0][byte.1] = Tile::Wall;
-        // we can save time by only checking for path completeness when the new
-        // byte was surrounded by two blocks, which means that it blocked a way
-        // through. though even with this optimisation, this is rather slow
-        if blocks_any_path(&area, byte.0, byte.1) && !has_path(&area, (0, 0), (70, 70)) {
-            return format!("{},{}", byte.0, byte.1);
+        if !path.contains(&byte) {
+            continue;
         }
+        let header = format!(
+            "Byte {} falls on {},{}",
+            memory.fallen + n + 1,
+            byte.0,
+            byte.1
+        );
+        match find_shortest_path(&area, (0, 0), memory.exit()) {
+            Some(new_path) => {
+                path = new_path;
+                frames.push(format!(
+                    "{header} and the path reroutes to {} steps:\n{}",
+                    path.len() - 1,
+                    render(&area, &path, None)
+                ));
+            }
+            None => {
+                frames.push(format!(
+                    "{header} and cuts off the exit:\n{}",
+                    render(&area, &path, Some(byte))
+                ));
+                break;
+            }
+        }
+    }
+    frames
+}
+
+/// The path after the bytes for part 1 have fallen, and the last path before
+/// the byte that cut off the exit, with that byte marked
+pub fn show_path(input: String, params: &Params) -> String {
+    let frames = fall(&input, Memory::new(params));
+    match frames.as_slice() {
+        [only] => only.clone(),
+        [first, .., last] => format!("{first}\n{last}"),
+        [] => unreachable!(),
     }
-    "-1".to_string()
+}
+
+/// Every time the path has to reroute as bytes fall, up to the one that cuts
+/// off the exit
+pub fn animate(input: String, params: &Params) -> Vec<String> {
+    fall(&input, Memory::new(params))
 }
 
 #[cfg(test)]
@@ -121,7 +377,7 @@ mod tests {
 
     #[test]
     fn sample_p1() {
-        use super::part1;
+        use super::{part1, Params};
 
         // sample input uses a smaller grid, so this is different from that even
         // if it is the same input
@@ -151,18 +407,18 @@ mod tests {
 1,6
 2,0"
         .to_string();
-        assert_eq!(part1(input), "146");
+        assert_eq!(part1(input, &Params::default()), "146");
 
         let almost_block_second_row = (0..71)
             .filter(|&x| x != 34)
             .map(|x| format!("{},{x}", 1))
             .join("\n");
-        assert_eq!(part1(almost_block_second_row), "140");
+        assert_eq!(part1(almost_block_second_row, &Params::default()), "140");
     }
 
     #[test]
     fn sample_p2() {
-        use super::part2;
+        use super::{part2, Params};
 
         let block_row_4 = (0..=70).map(|x| format!("{},{x}", 3));
         // actual input doesn't seem to have duplicates, unlike this, which has
@@ -171,6 +427,155 @@ mod tests {
             .map(|_| format!("{},{1}", 1, 1))
             .chain(block_row_4)
             .join("\n");
-        assert_eq!(part2(input), "3,70");
+        assert_eq!(part2(input, &Params::default()), "3,70");
+    }
+
+    const SAMPLE: &str = "5,4
+4,2
+4,5
+3,0
+2,1
+6,3
+2,4
+1,5
+0,6
+3,3
+2,6
+5,1
+1,2
+5,5
+2,5
+6,5
+1,4
+0,4
+6,4
+1,1
+6,1
+1,0
+0,5
+1,6
+2,0";
+
+    #[test]
+    fn sample_small_memory() {
+        use super::*;
+
+        let memory = Memory {
+            size: 7,
+            fallen: 12,
+        };
+        assert_eq!(shortest_path(SAMPLE, memory), Some(22));
+        let mut params = Params::default();
+        params.set("size", "7");
+        params.set("fallen", "12");
+        assert_eq!(part1(SAMPLE.to_string(), &params), "22");
+        assert_eq!(part2(SAMPLE.to_string(), &params), "6,1");
+        for strategy in [CutOff::BinarySearch, CutOff::UnionFind] {
+            assert_eq!(first_blocking_byte(SAMPLE, memory, strategy), Some((6, 1)));
+        }
+
+        // a bigger space than the puzzle's, with a wall down the middle that
+        // only has a gap at the bottom
+        let memory = Memory {
+            size: 201,
+            fallen: 200,
+        };
+        let wall = (0..200).map(|y| format!("{y},100")).join("\n");
+        assert_eq!(shortest_path(&wall, memory), Some(400));
+        let input = wall + "\n200,100";
+        for strategy in [CutOff::BinarySearch, CutOff::UnionFind] {
+            assert_eq!(
+                first_blocking_byte(&input, memory, strategy),
+                Some((200, 100))
+            );
+        }
+    }
+
+    #[test]
+    fn cut_off_strategies_agree() {
+        use super::*;
+        use crate::solvers::util::XorShift;
+
+        // bytes falling pseudo-randomly, with repeats, until they cut the exit
+        // off or everything has fallen
+        let memory = Memory {
+            size: 31,
+            fallen: 100,
+        };
+        let mut rng = XorShift::new(0x2545f491);
+        let mut bytes = vec![];
+        for _ in 0..31 * 31 {
+            let (y, x) = (rng.below(31) as usize, rng.below(31) as usize);
+            if (y, x) != (0, 0) && (y, x) != memory.exit() {
+                bytes.push(format!("{y},{x}"));
+            }
+        }
+        for count in [150, 300, 450, bytes.len()] {
+            let input = bytes[..count].join("\n");
+            let answers = [CutOff::BinarySearch, CutOff::UnionFind]
+                .map(|strategy| first_blocking_byte(&input, memory, strategy));
+            assert_eq!(answers[0], answers[1], "{count} bytes");
+        }
+        let input = bytes.join("\n");
+        assert!(first_blocking_byte(&input, memory, CutOff::UnionFind).is_some());
+
+        // a path that is already blocked before we start looking
+        let memory = Memory { size: 7, fallen: 7 };
+        let input = (0..7)
+            .map(|x| format!("3,{x}"))
+            .chain(["0,1".into()])
+            .join("\n");
+        for strategy in [CutOff::BinarySearch, CutOff::UnionFind] {
+            assert_eq!(first_blocking_byte(&input, memory, strategy), None);
+        }
+    }
+
+    #[test]
+    fn drawing_the_path() {
+        use super::*;
+
+        let memory = Memory {
+            size: 7,
+            fallen: 12,
+        };
+        let (_, area) = parse_input(SAMPLE, memory);
+        let path = find_shortest_path(&area, (0, 0), memory.exit()).unwrap();
+        assert_eq!(path.len(), 23);
+        assert_eq!((path[0], path[22]), ((0, 0), (6, 6)));
+        for (a, b) in path.iter().tuple_windows() {
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+        }
+
+        // the same walls as the puzzle's drawing, whichever path we took
+        let drawing = render(&area, &path, None);
+        assert_eq!(
+            drawing.replace('O', "."),
+            "...#...
+..#..#.
+....#..
+...#..#
+..#..#.
+.#..#..
+#.#....
+"
+        );
+        assert_eq!(drawing.matches('O').count(), 23);
+
+        let frames = fall(SAMPLE, memory);
+        assert!(frames[0].starts_with("After 12 bytes the shortest path takes 22 steps:\n"));
+        let last = frames.last().unwrap();
+        assert!(last.starts_with("Byte 21 falls on 6,1 and cuts off the exit:\n"));
+        // x is 6 and y is 1, so it's at the end of the second row
+        assert_eq!(last.lines().nth(2).unwrap().chars().nth(6), Some('X'));
+        for frame in &frames[1..frames.len() - 1] {
+            assert!(frame.contains("the path reroutes to"));
+        }
+
+        let blocked = Memory {
+            size: 7,
+            fallen: 21,
+        };
+        assert_eq!(fall(SAMPLE, blocked).len(), 1);
+        assert!(fall(SAMPLE, blocked)[0].starts_with("After 21 bytes there is no path:\n"));
     }
 }
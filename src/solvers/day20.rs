@@ -1,82 +1,250 @@
-use crate::solvers::util::adjacent_in_bounds;
+use std::{collections::VecDeque, ops::Range};
+
+use crate::solvers::{
+    params::{parses, Param, Params},
+    util::adjacent_in_bounds,
+};
+
+const CHEAT_P1: Param = Param {
+    key: "cheat_p1",
+    default: "2",
+    help: "how many picoseconds a cheat can last in part 1",
+    parse: parses::<usize>,
+};
+const CHEAT_P2: Param = Param {
+    key: "cheat_p2",
+    default: "20",
+    help: "how many picoseconds a cheat can last in part 2",
+    parse: parses::<usize>,
+};
+const MIN_SAVING: Param = Param {
+    key: "min_saving",
+    default: "100",
+    help: "how many picoseconds a cheat has to save to count",
+    parse: parses::<i64>,
+};
+pub const PARAMS: &[Param] = &[CHEAT_P1, CHEAT_P2, MIN_SAVING];
 
 #[derive(Debug, PartialEq, Eq)]
 enum Tile {
     Track,
     Wall,
 }
-struct RaceState {
-    pos: (usize, usize),
-    time: u64,
-}
-
-fn sum_all_cheats(
-    track: &Vec<Vec<Tile>>,
-    distances: &Vec<Vec<u64>>,
-    max_cheat_time: i64,
-    minimum_cheat_advantage: i64,
-) -> usize {
-    // TODO: optimise this as it is rather wasteful
-    let mut result = 0;
-    for y in 1..track.len() - 1 {
-        for x in 1..track.len() - 1 {
-            // we can't start from inside a wall
-            if distances[y][x] == u64::MAX {
-                continue;
-            }
-            result += cheat_advantages(
-                &track,
-                (y, x),
-                &distances,
-                max_cheat_time,
-                minimum_cheat_advantage,
-            );
+
+/// How long a cheat may last, and how much time it has to save to be counted
+#[derive(Debug, Clone, Copy)]
+struct CheatRules {
+    duration: usize,
+    min_saving: i64,
+}
+
+impl CheatRules {
+    fn new(params: &Params, duration: &Param) -> CheatRules {
+        CheatRules {
+            duration: params.get(duration),
+            min_saving: params.get(&MIN_SAVING),
         }
     }
-    result
-}
-
-fn cheat_advantages(
-    track: &Vec<Vec<Tile>>,
-    source: (usize, usize),
-    distances: &Vec<Vec<u64>>,
-    max_cheat_time: i64,
-    required_cheat_advantage: i64,
-) -> usize {
-    // a lot of the cheats produced overlap for different source points, which
-    // means it might be possible to cache them
-    let mut count = 0;
-    let legal_range = 0..track.len() as i64;
-
-    // we want to have a total of up to max_cheat_time spread between dy and dx
-    for dy in (-max_cheat_time)..=max_cheat_time {
-        let target_y = source.0 as i64 + dy;
-        let remainder = max_cheat_time - dy.abs();
-        for dx in (-remainder)..=remainder {
-            let target_x = source.1 as i64 + dx;
-
-            if !legal_range.contains(&target_y) || !legal_range.contains(&target_x) {
-                continue;
-            }
-            let target_y = target_y as usize;
-            let target_x = target_x as usize;
+}
 
-            let target_distance = distances[target_y][target_x];
-            // if target is a wall, there's no point calculating anything else
-            if target_distance == u64::MAX {
-                continue;
-            }
+type Position = (usize, usize);
 
-            let source_distance = distances[source.0][source.1];
-            let cheat_time = dy.abs() + dx.abs();
-            let cheat_advantage = target_distance as i64 - source_distance as i64 - cheat_time;
+// the path is split into segments this long, which are spread over threads
+const SEGMENT_LEN: usize = 256;
 
-            if cheat_advantage >= required_cheat_advantage {
-                count += 1;
-            }
+fn race_path(distances: &[Vec<u64>], end: Position) -> Vec<Position> {
+    // walk back from the end, always to a tile one closer to the start, which
+    // gives us the shortest route even if the track branches
+    assert_ne!(
+        distances[end.0][end.1],
+        u64::MAX,
+        "the end can't be reached"
+    );
+    let mut path = vec![end];
+    let mut current = end;
+    while distances[current.0][current.1] > 0 {
+        current = adjacent_in_bounds(current.0, current.1, distances.len())
+            .find(|&(y, x)| distances[y][x].wrapping_add(1) == distances[current.0][current.1])
+            .unwrap();
+        path.push(current);
+    }
+    path.reverse();
+    path
+}
+
+/// Where each tile is along the path, padded with walls on every side so that
+/// looking up to a cheat's length away never leaves the grid
+struct PathIndex {
+    padding: usize,
+    width: usize,
+    // distance from the start for each tile, u32::MAX for walls
+    tiles: Vec<u32>,
+    // the rows of tiles a cheat can reach, as where they start from the
+    // padding to the left of the tile the cheat starts from, and which part
+    // of cheat_times goes with them
+    rows: Vec<(usize, Range<usize>)>,
+    cheat_times: Vec<u32>,
+}
+
+impl PathIndex {
+    fn new(path: &[Position], duration: usize) -> PathIndex {
+        let padding = duration;
+        let size = path.iter().map(|&(y, x)| y.max(x)).max().unwrap_or(0) + 1;
+        let width = size + 2 * padding;
+        let mut tiles = vec![u32::MAX; width * width];
+        for (distance, &(y, x)) in path.iter().enumerate() {
+            tiles[(y + padding) * width + x + padding] = distance as u32;
+        }
+
+        // the tiles a cheat can reach form a diamond. every pair of tiles in
+        // reach of each other would be seen from both ends, so we only look at
+        // the half of the diamond to the right of and below the tile
+        let mut rows = vec![];
+        let mut cheat_times = vec![];
+        for dy in 0..=duration {
+            let remainder = duration - dy;
+            let dxs = match dy {
+                0 => 1..=duration as isize,
+                _ => -(remainder as isize)..=remainder as isize,
+            };
+            let start = dy * width + (duration as isize + dxs.start()) as usize;
+            let times = cheat_times.len()..cheat_times.len() + dxs.clone().count();
+            cheat_times.extend(dxs.map(|dx| (dy + dx.unsigned_abs()) as u32));
+            rows.push((start, times));
+        }
+        PathIndex {
+            padding,
+            width,
+            tiles,
+            rows,
+            cheat_times,
         }
     }
-    count
+
+    /// Runs of tiles that can be reached by a cheat, with how long the cheat
+    /// to each tile takes
+    fn reachable(&self, (y, x): Position) -> impl Iterator<Item = (&[u32], &[u32])> {
+        let left = (y + self.padding) * self.width + x;
+        self.rows.iter().map(move |(start, times)| {
+            let start = left + start;
+            (
+                &self.tiles[start..start + times.len()],
+                &self.cheat_times[times.clone()],
+            )
+        })
+    }
+}
+
+fn savings<'a>(
+    from: usize,
+    (tiles, cheat_times): (&'a [u32], &'a [u32]),
+) -> impl Iterator<Item = u32> + 'a {
+    // the cheat goes from whichever tile comes first. walls are so far along
+    // that the saving is always out of range
+    let from = from as u32;
+    tiles
+        .iter()
+        .zip(cheat_times)
+        .map(move |(&distance, &cheat_time)| distance.abs_diff(from).wrapping_sub(cheat_time))
+}
+
+/// Savings that count are in [min_saving, race length), which a single
+/// comparison checks for, as anything below wraps around
+fn saving_range(rules: CheatRules, path: &[Position]) -> (u32, u32) {
+    let min_saving = (rules.min_saving.max(1) as u32).min(path.len() as u32);
+    (min_saving, path.len() as u32 - min_saving)
+}
+
+fn over_segments<T: Send>(
+    path: &[Position],
+    work: impl Fn(Range<usize>) -> T + Sync,
+    combine: impl Fn(T, T) -> T,
+) -> Option<T> {
+    // later segments can have fewer tiles near them that are ahead, so the
+    // threads take turns picking segments rather than each getting one long
+    // stretch
+    let segments = path.len().div_ceil(SEGMENT_LEN);
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(segments.max(1));
+    let work = &work;
+    std::thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|thread| {
+                scope.spawn(move || {
+                    (thread..segments)
+                        .step_by(threads)
+                        .map(|segment| {
+                            work(
+                                segment * SEGMENT_LEN
+                                    ..((segment + 1) * SEGMENT_LEN).min(path.len()),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .reduce(combine)
+    })
+}
+
+fn count_cheats_on_path(path: &[Position], rules: CheatRules) -> usize {
+    let index = PathIndex::new(path, rules.duration);
+    let (min_saving, range) = saving_range(rules, path);
+    let work = |starts: Range<usize>| {
+        let mut count = 0;
+        for from in starts {
+            for run in index.reachable(path[from]) {
+                // summing up the run on its own lets this vectorise, as this
+                // is the hot loop
+                count += savings(from, run)
+                    .map(|saving| (saving.wrapping_sub(min_saving) < range) as usize)
+                    .sum::<usize>();
+            }
+        }
+        count
+    };
+    over_segments(path, work, |a, b| a + b).unwrap_or(0)
+}
+
+fn savings_histogram(path: &[Position], rules: CheatRules) -> Vec<(i64, usize)> {
+    // cheats only make sense from a tile on the path to a tile further along
+    // it, so we go along the path and look up what is around each tile.
+    // savings can't be larger than the whole race, so the counts fit in a plain
+    // array indexed by time saved
+    let index = PathIndex::new(path, rules.duration);
+    let (min_saving, range) = saving_range(rules, path);
+    let work = |starts: Range<usize>| {
+        let mut counts = vec![0; path.len()];
+        for from in starts {
+            for saving in index
+                .reachable(path[from])
+                .flat_map(|run| savings(from, run))
+            {
+                if saving.wrapping_sub(min_saving) < range {
+                    counts[saving as usize] += 1;
+                }
+            }
+        }
+        counts
+    };
+    let combine = |mut total: Vec<usize>, counts: Vec<usize>| {
+        for (total, count) in total.iter_mut().zip(counts) {
+            *total += count;
+        }
+        total
+    };
+    over_segments(path, work, combine)
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .map(|(saving, count)| (saving as i64, count))
+        .collect()
 }
 
 fn parse_input(input: String) -> (Vec<Vec<Tile>>, (usize, usize), (usize, usize)) {
@@ -112,58 +280,225 @@ fn parse_input(input: String) -> (Vec<Vec<Tile>>, (usize, usize), (usize, usize)
     (track, start, end)
 }
 
-fn calculate_distances(
-    track: &Vec<Vec<Tile>>,
-    start: (usize, usize),
-    end: (usize, usize),
-) -> Vec<Vec<u64>> {
-    let mut state = RaceState {
-        pos: start,
-        time: 0,
-    };
-
+fn calculate_distances(track: &[Vec<Tile>], source: Position) -> Vec<Vec<u64>> {
+    // simple bfs, so the track can branch off or loop around
     let mut distances = vec![vec![u64::MAX; track.len()]; track.len()];
-    distances[start.0][start.1] = 0;
+    distances[source.0][source.1] = 0;
+    let mut queue = VecDeque::from([source]);
 
-    while state.pos != end {
-        for pos in adjacent_in_bounds(state.pos.0, state.pos.1, track.len()) {
+    while let Some(current) = queue.pop_front() {
+        for pos in adjacent_in_bounds(current.0, current.1, track.len()) {
             // let's not crash
-            if track[pos.0][pos.1] == Tile::Wall {
+            if track[pos.0][pos.1] == Tile::Wall || distances[pos.0][pos.1] != u64::MAX {
                 continue;
             }
+            distances[pos.0][pos.1] = distances[current.0][current.1] + 1;
+            queue.push_back(pos);
+        }
+    }
+    distances
+}
 
-            if distances[pos.0][pos.1] == u64::MAX {
-                distances[pos.0][pos.1] = state.time + 1;
+/// A cheat from the last tile on the track before going through walls to the
+/// first tile back on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cheat {
+    start: Position,
+    end: Position,
+    length: usize,
+    saved: usize,
+}
 
-                state = RaceState {
-                    pos,
-                    time: state.time + 1,
-                };
-            }
+struct Race {
+    track: Vec<Vec<Tile>>,
+    from_start: Vec<Vec<u64>>,
+    to_end: Vec<Vec<u64>>,
+    path: Vec<Position>,
+}
+
+impl Race {
+    fn new(input: String) -> Race {
+        let (track, start, end) = parse_input(input);
+        let from_start = calculate_distances(&track, start);
+        let to_end = calculate_distances(&track, end);
+        let path = race_path(&from_start, end);
+        Race {
+            track,
+            from_start,
+            to_end,
+            path,
         }
     }
-    distances
+
+    fn is_corridor(&self) -> bool {
+        // then every tile we can get to is on the way to the end
+        let reachable = self.from_start.iter().flatten();
+        reachable.filter(|&&distance| distance != u64::MAX).count() == self.path.len()
+    }
+
+    /// Every cheat that saves enough time, each one only once for each start
+    /// and end
+    fn cheats(&self, rules: CheatRules) -> impl Iterator<Item = Cheat> + '_ {
+        let size = self.track.len() as i64;
+        let duration = rules.duration as i64;
+        let best = self.path.len() as i64 - 1;
+        let min_saving = rules.min_saving.max(1);
+        let starts =
+            (0..self.track.len()).flat_map(move |y| (0..self.track.len()).map(move |x| (y, x)));
+        starts
+            .filter(|&(y, x)| self.from_start[y][x] != u64::MAX)
+            .flat_map(move |start| {
+                (-duration..=duration).flat_map(move |dy| {
+                    let remainder = duration - dy.abs();
+                    (-remainder..=remainder).filter_map(move |dx| {
+                        let (y, x) = (start.0 as i64 + dy, start.1 as i64 + dx);
+                        if !(0..size).contains(&y) || !(0..size).contains(&x) || (dy, dx) == (0, 0)
+                        {
+                            return None;
+                        }
+                        let end = (y as usize, x as usize);
+                        let to_end = self.to_end[end.0][end.1];
+                        // if the end is a wall, there's no point calculating anything else
+                        if to_end == u64::MAX {
+                            return None;
+                        }
+                        let length = dy.abs() + dx.abs();
+                        let time =
+                            self.from_start[start.0][start.1] as i64 + length + to_end as i64;
+                        (best - time >= min_saving).then_some(Cheat {
+                            start,
+                            end,
+                            length: length as usize,
+                            saved: (best - time) as usize,
+                        })
+                    })
+                })
+            })
+    }
+
+    fn histogram(&self, rules: CheatRules) -> Vec<(i64, usize)> {
+        if self.is_corridor() {
+            return savings_histogram(&self.path, rules);
+        }
+        let mut counts = vec![0; self.path.len()];
+        for cheat in self.cheats(rules) {
+            counts[cheat.saved] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .map(|(saving, count)| (saving as i64, count))
+            .collect()
+    }
+
+    fn count(&self, rules: CheatRules) -> usize {
+        match self.is_corridor() {
+            true => count_cheats_on_path(&self.path, rules),
+            false => self.cheats(rules).count(),
+        }
+    }
+
+    fn render(&self, cheats: &[Cheat]) -> String {
+        const LABELS: &[u8] = b"123456789abcdefghijklmnopqrstuvwxyz";
+        let mut grid = self
+            .track
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Tile::Wall => b'#',
+                        Tile::Track => b'.',
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let (start, end) = (self.path[0], self.path[self.path.len() - 1]);
+        grid[start.0][start.1] = b'S';
+        grid[end.0][end.1] = b'E';
+        let mut legend = String::new();
+        for (cheat, &label) in cheats.iter().zip(LABELS) {
+            grid[cheat.start.0][cheat.start.1] = label;
+            grid[cheat.end.0][cheat.end.1] = label;
+            legend += &format!(
+                "{}: from row {} column {} to row {} column {} in {} picoseconds, saving {}\n",
+                label as char,
+                cheat.start.0,
+                cheat.start.1,
+                cheat.end.0,
+                cheat.end.1,
+                cheat.length,
+                cheat.saved
+            );
+        }
+        let mut out = grid
+            .into_iter()
+            .map(|row| String::from_utf8(row).unwrap() + "\n")
+            .collect::<String>();
+        out += &legend;
+        out
+    }
 }
 
-pub fn part1(input: String) -> String {
-    let (track, start, end) = parse_input(input);
-    let distances = calculate_distances(&track, start, end);
-    sum_all_cheats(&track, &distances, 2, 100).to_string()
+fn best_cheats(race: &Race, rules: CheatRules, count: usize) -> Vec<Cheat> {
+    let mut cheats = race.cheats(rules).collect::<Vec<_>>();
+    cheats.sort_by_key(|cheat| (std::cmp::Reverse(cheat.saved), cheat.start, cheat.end));
+    cheats.truncate(count);
+    cheats
 }
 
-pub fn part2(input: String) -> String {
-    let (track, start, end) = parse_input(input);
-    let distances = calculate_distances(&track, start, end);
-    sum_all_cheats(&track, &distances, 20, 100).to_string()
+/// The track with the cheats that save the most time marked on it, for each
+/// part
+pub fn show_best_cheats(input: String, count: usize, params: &Params) -> String {
+    let race = Race::new(input);
+    let mut out = String::new();
+    for (part, duration) in [(1, &CHEAT_P1), (2, &CHEAT_P2)] {
+        let rules = CheatRules::new(params, duration);
+        out += &format!(
+            "Part {part}, the best cheats of up to {} picoseconds:\n",
+            rules.duration
+        );
+        out += &race.render(&best_cheats(&race, rules, count));
+    }
+    out
+}
+
+fn count_cheats(input: String, rules: CheatRules) -> usize {
+    Race::new(input).count(rules)
+}
+
+/// How many cheats save each amount of time, for each part, worded like the
+/// puzzle's examples
+pub fn savings_report(input: String, params: &Params) -> String {
+    let race = Race::new(input);
+    let mut out = String::new();
+    for (part, duration) in [(1, &CHEAT_P1), (2, &CHEAT_P2)] {
+        let rules = CheatRules::new(params, duration);
+        out += &format!(
+            "Part {part}, cheats of up to {} picoseconds saving at least {}:\n",
+            rules.duration, rules.min_saving
+        );
+        for (saving, count) in race.histogram(rules) {
+            out += &match count {
+                1 => format!("There is one cheat that saves {saving} picoseconds.\n"),
+                _ => format!("There are {count} cheats that save {saving} picoseconds.\n"),
+            };
+        }
+    }
+    out
+}
+
+pub fn part1(input: String, params: &Params) -> String {
+    count_cheats(input, CheatRules::new(params, &CHEAT_P1)).to_string()
+}
+
+pub fn part2(input: String, params: &Params) -> String {
+    count_cheats(input, CheatRules::new(params, &CHEAT_P2)).to_string()
 }
 
 #[cfg(test)]
 mod tests {
-    #[test]
-    fn sample_p1() {
-        use super::part1;
-
-        let input = "###############
+    const SAMPLE: &str = "###############
 #...#...#.....#
 #.#.#.#.#.###.#
 #S#...#.#.#...#
@@ -177,32 +512,220 @@ mod tests {
 #.#...#.#.#...#
 #.#.#.#.#.#.###
 #...#...#...###
-###############"
-            .to_string();
+###############";
+
+    #[test]
+    fn sample_p1() {
+        use super::{part1, Params};
+
+        let input = SAMPLE.to_string();
         // input too small for result to be higher
-        assert_eq!(part1(input), "0");
+        assert_eq!(part1(input, &Params::default()), "0");
     }
 
     #[test]
     fn sample_p2() {
-        use super::part2;
+        use super::{part2, Params};
 
-        let input = "###############
-#...#...#.....#
-#.#.#.#.#.###.#
-#S#...#.#.#...#
-#######.#.#.###
-#######.#.#...#
-#######.#.###.#
-###..E#...#...#
-###.#######.###
-#...###...#...#
-#.#####.#.###.#
-#.#...#.#.#...#
-#.#.#.#.#.#.###
-#...#...#...###
-###############"
+        let input = SAMPLE.to_string();
+        assert_eq!(part2(input, &Params::default()), "0");
+    }
+
+    #[test]
+    fn savings_tables() {
+        use super::*;
+
+        let input = SAMPLE.to_string();
+        let (track, start, end) = parse_input(input.clone());
+        let path = race_path(&calculate_distances(&track, start), end);
+        assert_eq!(path.len(), 85);
+        assert_eq!((path[0], path[84]), (start, end));
+
+        let every_cheat = CheatRules {
+            duration: 2,
+            min_saving: 0,
+        };
+        assert_eq!(
+            savings_histogram(&path, every_cheat),
+            [
+                (2, 14),
+                (4, 14),
+                (6, 2),
+                (8, 4),
+                (10, 2),
+                (12, 3),
+                (20, 1),
+                (36, 1),
+                (38, 1),
+                (40, 1),
+                (64, 1)
+            ]
+        );
+
+        let long_cheats = CheatRules {
+            duration: 20,
+            min_saving: 50,
+        };
+        assert_eq!(
+            savings_histogram(&path, long_cheats),
+            [
+                (50, 32),
+                (52, 31),
+                (54, 29),
+                (56, 39),
+                (58, 25),
+                (60, 23),
+                (62, 20),
+                (64, 19),
+                (66, 12),
+                (68, 14),
+                (70, 12),
+                (72, 22),
+                (74, 4),
+                (76, 3)
+            ]
+        );
+        assert_eq!(count_cheats(input.clone(), long_cheats), 285);
+
+        let mut params = Params::default();
+        params.set("min_saving", "50");
+        assert_eq!(part2(input.clone(), &params), "285");
+        params.set("min_saving", "20");
+        assert_eq!(part1(input, &params), "5");
+    }
+
+    #[test]
+    fn all_cheat_lengths() {
+        use super::*;
+
+        let input = SAMPLE.to_string();
+        let (track, start, end) = parse_input(input);
+        let path = race_path(&calculate_distances(&track, start), end);
+
+        // checking every pair of tiles along the path
+        for duration in 0..30 {
+            for min_saving in [0, 1, 10, 50, 80] {
+                let mut expected = vec![0; path.len()];
+                for (from, &a) in path.iter().enumerate() {
+                    for (to, &b) in path.iter().enumerate().skip(from + 1) {
+                        let cheat_time = a.0.abs_diff(b.0) + a.1.abs_diff(b.1);
+                        let saving = (to - from).saturating_sub(cheat_time);
+                        if cheat_time <= duration && saving >= min_saving.max(1) {
+                            expected[saving] += 1;
+                        }
+                    }
+                }
+                let expected = expected
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(_, count)| count > 0)
+                    .map(|(saving, count)| (saving as i64, count))
+                    .collect::<Vec<_>>();
+                let rules = CheatRules {
+                    duration,
+                    min_saving: min_saving as i64,
+                };
+                assert_eq!(savings_histogram(&path, rules), expected);
+                assert_eq!(
+                    count_cheats_on_path(&path, rules),
+                    expected.iter().map(|(_, count)| count).sum::<usize>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cheat_records() {
+        use super::*;
+        use rustc_hash::FxHashSet;
+
+        let input = SAMPLE.to_string();
+        let race = Race::new(input);
+        assert!(race.is_corridor());
+
+        // the records agree with the counts for the corridor
+        let rules = CheatRules {
+            duration: 20,
+            min_saving: 50,
+        };
+        let cheats = race.cheats(rules).collect::<Vec<_>>();
+        assert_eq!(cheats.len(), 285);
+        let ends = cheats
+            .iter()
+            .map(|cheat| (cheat.start, cheat.end))
+            .collect::<FxHashSet<_>>();
+        assert_eq!(ends.len(), cheats.len());
+        let saving_76 = cheats.iter().filter(|cheat| cheat.saved == 76);
+        assert_eq!(saving_76.count(), 3);
+
+        // the puzzle's best two picosecond cheat
+        let rules = CheatRules {
+            duration: 2,
+            min_saving: 0,
+        };
+        let best = best_cheats(&race, rules, 1);
+        assert_eq!(
+            best,
+            [Cheat {
+                start: (7, 7),
+                end: (7, 5),
+                length: 2,
+                saved: 64
+            }]
+        );
+        let drawing = race.render(&best);
+        assert_eq!(drawing.lines().nth(7), Some("###..1#1..#...#"));
+        assert!(drawing
+            .ends_with("1: from row 7 column 7 to row 7 column 5 in 2 picoseconds, saving 64\n"));
+    }
+
+    #[test]
+    fn branching_track() {
+        use super::*;
+
+        // two equally short ways around the loop, and a dead end
+        let input = "#######
+#S..#E#
+#.#.#.#
+#...#.#
+#.#...#
+#.#####
+#######"
             .to_string();
-        assert_eq!(part2(input), "0");
+        let race = Race::new(input.clone());
+        assert!(!race.is_corridor());
+        assert_eq!(race.path.len(), 11);
+        assert_eq!(race.path[10], (1, 5));
+
+        let rules = CheatRules {
+            duration: 2,
+            min_saving: 0,
+        };
+        let mut cheats = race.cheats(rules).collect::<Vec<_>>();
+        cheats.sort_by_key(|cheat| cheat.saved);
+        let cheats = cheats
+            .into_iter()
+            .map(|cheat| (cheat.start, cheat.end, cheat.saved))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cheats,
+            [
+                ((3, 3), (3, 5), 2),
+                ((2, 3), (2, 5), 4),
+                ((1, 3), (1, 5), 6)
+            ]
+        );
+        assert_eq!(race.histogram(rules), [(2, 1), (4, 1), (6, 1)]);
+        assert_eq!(count_cheats(input, rules), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value -1 for cheat_p2")]
+    fn negative_cheat_duration() {
+        use super::{part2, Params};
+
+        let mut params = Params::default();
+        params.set("cheat_p2", "-1");
+        part2("S.E".to_string(), &params);
     }
 }
@@ -1,206 +1,414 @@
-use itertools::Itertools;
+use std::fmt;
+
 use rustc_hash::FxHashMap;
 
-fn numpad_pos(c: char) -> (i32, i32) {
-    // 7 8 9
-    // 4 5 6
-    // 1 2 3
-    //   0 A
-    match c {
-        '7' => (0, 0),
-        '8' => (0, 1),
-        '9' => (0, 2),
-        '4' => (1, 0),
-        '5' => (1, 1),
-        '6' => (1, 2),
-        '1' => (2, 0),
-        '2' => (2, 1),
-        '3' => (2, 2),
-        '0' => (3, 1),
-        'A' => (3, 2),
-        _ => unreachable!(),
-    }
-}
-fn dirpad_pos(c: char) -> (i32, i32) {
-    //   ^ A
-    // < v >
-    match c {
-        '^' => (0, 1),
-        'A' => (0, 2),
-        '<' => (1, 0),
-        'v' => (1, 1),
-        '>' => (1, 2),
-        _ => {
-            println!("{c}");
-            unreachable!()
-        }
-    }
-}
-fn move_vertical(y: i32, y_target: i32, output: &mut String) {
-    if y == y_target {
-        return;
-    }
-    let count = y.abs_diff(y_target);
-    let c = if y < y_target { 'v' } else { '^' };
-    (0..count).for_each(|_| output.push(c));
-}
-fn move_horizontal(x: i32, x_target: i32, output: &mut String) {
-    if x == x_target {
-        return;
-    }
-    let count = x.abs_diff(x_target);
-    let c = if x < x_target { '>' } else { '<' };
-    (0..count).for_each(|_| output.push(c));
-}
-fn dirpad_path_to(c: char, state: &mut (i32, i32), output: &mut String) {
-    // the order of movement we want to execute is left/up/down/right in order
-    // of preference [1] while also avoiding going over the forbidden spot,
-    // which doesn't have a button and would break our bots
-
-    // [1]: https://old.reddit.com/r/adventofcode/comments/1hjgyps/2024_day_21_part_2_i_got_greedyish/
-
-    let target = dirpad_pos(c);
-    // starting from (1, 0) => avoid going over forbidden spot
-    if state.1 == 0 {
-        move_horizontal(state.1, target.1, output);
-        move_vertical(state.0, target.0, output);
-    // going to (1, 0) => avoid going over forbidden spot
-    } else if target.1 == 0 {
-        move_vertical(state.0, target.0, output);
-        move_horizontal(state.1, target.1, output);
-        // going left
-    } else if target.1 < state.1 {
-        move_horizontal(state.1, target.1, output);
-        move_vertical(state.0, target.0, output);
-    } else {
-        move_vertical(state.0, target.0, output);
-        move_horizontal(state.1, target.1, output);
-    }
-
-    *state = target;
-}
-fn numpad_path_to(c: char, state: &mut (i32, i32), output: &mut String) {
-    // the order of movement we want to execute is left/up/down/right in order
-    // of preference [1] while also avoiding going over the forbidden spot,
-    // which doesn't have a button and would break our bots
-
-    // [1]: https://old.reddit.com/r/adventofcode/comments/1hjgyps/2024_day_21_part_2_i_got_greedyish/
-    let target = numpad_pos(c);
-
-    //starting from (_, 0) (left col) and going to (3, _) (bottom row) => avoid
-    // going over forbidden spot
-    if state.1 == 0 && target.0 == 3 {
-        move_horizontal(state.1, target.1, output);
-        move_vertical(state.0, target.0, output);
-    // starting from (3, _) (bottom row) and going to (_, 0) (left col) => avoid
-    // going over forbidden spot
-    } else if state.0 == 3 && target.1 == 0 {
-        move_vertical(state.0, target.0, output);
-        move_horizontal(state.1, target.1, output);
-    } else if target.1 < state.1 {
-        move_horizontal(state.1, target.1, output);
-        move_vertical(state.0, target.0, output);
-    } else {
-        move_vertical(state.0, target.0, output);
-        move_horizontal(state.1, target.1, output);
-    }
-    *state = target;
-}
-fn numpad_moves(code: &str) -> String {
-    let mut output = String::new();
-    // robot starts on the button 'A'
-    let mut state = numpad_pos('A');
-    for number in code.chars() {
-        // for each part of the code (including the 'A'), we navigate the
-        // robotic finger to that button, and press it (A)
-        numpad_path_to(number, &mut state, &mut output);
-        output.push('A');
+use super::params::{parses, Param, Params};
+
+const ROBOTS_P1: Param = Param {
+    key: "robots_p1",
+    default: "2",
+    help: "how many robots use directional keypads in part 1",
+    parse: parses::<usize>,
+};
+const ROBOTS_P2: Param = Param {
+    key: "robots_p2",
+    default: "25",
+    help: "how many robots use directional keypads in part 2",
+    parse: parses::<usize>,
+};
+pub const PARAMS: &[Param] = &[ROBOTS_P1, ROBOTS_P2];
+
+// the keypad on the door
+const NUMERIC: &str = "789
+456
+123
+ 0A";
+// the keypad every robot past the first is directed with, and the one we press
+const DIRECTIONAL: &str = " ^A
+<v>";
+
+// row and column on a keypad
+type Position = (i32, i32);
+
+#[derive(Debug, Clone)]
+struct Keypad {
+    buttons: FxHashMap<char, Position>,
+    layout: FxHashMap<Position, char>,
+}
+
+impl Keypad {
+    /// Reads a keypad drawn as rows of buttons, with spaces where there is no
+    /// button for the robot arm to point at
+    fn parse(drawing: &str) -> Keypad {
+        let mut buttons = FxHashMap::default();
+        let mut layout = FxHashMap::default();
+        for (y, row) in drawing.lines().enumerate() {
+            for (x, button) in row.chars().enumerate() {
+                if button == ' ' {
+                    continue;
+                }
+                let position = (y as i32, x as i32);
+                if buttons.insert(button, position).is_some() {
+                    panic!("the button {button} is on the keypad twice");
+                }
+                layout.insert(position, button);
+            }
+        }
+        Keypad { buttons, layout }
     }
-    output
+
+    fn position(&self, button: char) -> Position {
+        *self
+            .buttons
+            .get(&button)
+            .unwrap_or_else(|| panic!("there is no button {button} on the keypad"))
+    }
+
+    /// Every shortest way to move the arm from one button to another and
+    /// press it without ever pointing at a gap. Most of them zig-zag, which is
+    /// never cheaper for the robot directing this one, but on some keypads
+    /// they are all there is
+    fn moves(&self, from: char, to: char) -> Vec<String> {
+        fn walk(
+            keypad: &Keypad,
+            at: Position,
+            target: Position,
+            path: &mut String,
+            moves: &mut Vec<String>,
+        ) {
+            if !keypad.layout.contains_key(&at) {
+                return;
+            }
+            if at == target {
+                moves.push(format!("{path}A"));
+                return;
+            }
+            let steps = [
+                ('<', (0, -1), at.1 > target.1),
+                ('^', (-1, 0), at.0 > target.0),
+                ('v', (1, 0), at.0 < target.0),
+                ('>', (0, 1), at.1 < target.1),
+            ];
+            for (step, (dy, dx), towards) in steps {
+                if towards {
+                    path.push(step);
+                    walk(keypad, (at.0 + dy, at.1 + dx), target, path, moves);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut moves = vec![];
+        walk(
+            self,
+            self.position(from),
+            self.position(to),
+            &mut String::new(),
+            &mut moves,
+        );
+        moves
+    }
+}
+
+/// A robot arm that pointed at a gap, which makes the robot panic
+#[derive(Debug, PartialEq)]
+struct Gap {
+    keypad: usize,
+    press: usize,
 }
 
-fn calculate_button_press_count(numpad_result: String, iterations_after_first: u32) -> usize {
-    // robot starts on the button 'A'
-    let chars = ['<', '>', '^', 'v', 'A'];
-    // pre-calculate amount of button presses needed for all moves (i.e. from
-    // button '>' to '<')
-    let mut count_map = FxHashMap::from_iter(chars.iter().permutations(2).map(|vec| {
-        let mut output = String::new();
-        dirpad_path_to(*vec[1], &mut dirpad_pos(*vec[0]), &mut output);
-        output.push('A');
-        ((*vec[0], *vec[1]), output.len())
-    }));
-
-    // sum the cost of a set of movements on a direction pad, starting from 'A'
-    let sum_path = |moves: String, count_map: &FxHashMap<(char, char), usize>| {
-        // intermediate bots always start and end on the 'A' button
-        let mut prev = 'A';
-        let mut n = 0;
-        for c in moves.chars() {
-            n += if prev == c {
-                // moving to the same button would simply indicate another 'A'
-                // press when the bots are lined up
-                1
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "press {} points the arm over keypad {} at a gap",
+            self.press + 1,
+            self.keypad
+        )
+    }
+}
+
+/// The keypads robots press, starting with the one on the door. Each robot
+/// is directed with the keypad after its own, and we press buttons on a
+/// directional keypad to direct the last one
+struct Chain {
+    keypads: Vec<Keypad>,
+    // for each keypad, how many presses we need to make for its robot to move
+    // from one button to another and press it, or None if that doesn't fit in
+    // a u128
+    costs: Vec<FxHashMap<(char, char), Option<u128>>>,
+}
+
+impl Chain {
+    fn new(keypads: Vec<Keypad>) -> Chain {
+        assert!(!keypads.is_empty(), "a chain needs at least one keypad");
+        for keypad in &keypads[1..] {
+            for button in ['^', 'v', '<', '>', 'A'] {
+                keypad.position(button);
+            }
+        }
+
+        // the costs of each keypad follow from the costs of the one after
+        // it, so we work our way back from our own presses to the door
+        let mut chain = Chain {
+            costs: vec![FxHashMap::default(); keypads.len()],
+            keypads,
+        };
+        for level in (0..chain.keypads.len()).rev() {
+            let keypad = &chain.keypads[level];
+            let mut costs = FxHashMap::default();
+            for &from in keypad.buttons.keys() {
+                for &to in keypad.buttons.keys() {
+                    let ways = keypad.moves(from, to);
+                    assert!(!ways.is_empty(), "there is no way from {from} to {to}");
+                    let cost = ways
+                        .iter()
+                        .filter_map(|moves| chain.cost(level + 1, moves))
+                        .min();
+                    costs.insert((from, to), cost);
+                }
+            }
+            chain.costs[level] = costs;
+        }
+        chain
+    }
+
+    /// The puzzle's chain, with a numeric keypad on the door and some
+    /// number of robots using directional keypads in between
+    fn puzzle(robots: usize) -> Chain {
+        let mut keypads = vec![Keypad::parse(NUMERIC)];
+        keypads.extend((0..robots).map(|_| Keypad::parse(DIRECTIONAL)));
+        Chain::new(keypads)
+    }
+
+    /// How many presses we need to make for the robot at the given keypad to
+    /// press the sequence of buttons, starting on A. Past the last keypad
+    /// that is us, who press each button just once. None if there are too
+    /// many to count in a u128
+    fn cost(&self, level: usize, sequence: &str) -> Option<u128> {
+        if level == self.keypads.len() {
+            return Some(sequence.len() as u128);
+        }
+        let mut from = 'A';
+        let mut cost = 0u128;
+        for to in sequence.chars() {
+            cost = cost.checked_add(self.costs[level][&(from, to)]?)?;
+            from = to;
+        }
+        Some(cost)
+    }
+
+    /// The fewest presses we need to make for the door to be sent the code
+    fn count(&self, code: &str) -> Option<u128> {
+        self.cost(0, code)
+    }
+
+    /// One of the shortest sequences of presses that sends the code to the
+    /// door. They get about two and a half times longer for every robot, so
+    /// this is only for short chains, which can always be counted
+    fn presses(&self, code: &str) -> String {
+        let mut presses = String::new();
+        self.expand(0, code, &mut presses);
+        presses
+    }
+
+    fn expand(&self, level: usize, sequence: &str, presses: &mut String) {
+        let mut from = 'A';
+        for to in sequence.chars() {
+            let moves = self.keypads[level]
+                .moves(from, to)
+                .into_iter()
+                .min_by_key(|moves| self.cost(level + 1, moves).unwrap_or(u128::MAX))
+                .unwrap();
+            if level + 1 == self.keypads.len() {
+                presses.push_str(&moves);
             } else {
-                *count_map.get(&(prev, c)).unwrap()
-            };
-            prev = c;
+                self.expand(level + 1, &moves, presses);
+            }
+            from = to;
         }
-        n
-    };
+    }
 
-    let mut count_map2 = FxHashMap::default();
-    for _ in 0..iterations_after_first {
-        chars.iter().permutations(2).for_each(|vec| {
-            let mut output = String::new();
-            // get base route to other to get characters to iterate over
-            dirpad_path_to(*vec[1], &mut dirpad_pos(*vec[0]), &mut output);
-            output.push('A');
+    /// Replays our presses through every robot, returning what they send to
+    /// the door
+    fn replay(&self, presses: &str) -> Result<String, Gap> {
+        let mut arms = self
+            .keypads
+            .iter()
+            .map(|keypad| keypad.position('A'))
+            .collect::<Vec<_>>();
+        let mut sent = String::new();
+        'presses: for (press, mut button) in presses.chars().enumerate() {
+            for keypad in (0..self.keypads.len()).rev() {
+                let arm = &mut arms[keypad];
+                let (dy, dx) = match button {
+                    '^' => (-1, 0),
+                    'v' => (1, 0),
+                    '<' => (0, -1),
+                    '>' => (0, 1),
+                    'A' => {
+                        // the robot presses the button its arm points at,
+                        // which the robot it directs acts on in turn
+                        button = self.keypads[keypad].layout[&*arm];
+                        continue;
+                    }
+                    _ => panic!("there is no button {button} to direct a robot with"),
+                };
+                *arm = (arm.0 + dy, arm.1 + dx);
+                if !self.keypads[keypad].layout.contains_key(arm) {
+                    return Err(Gap { keypad, press });
+                }
+                continue 'presses;
+            }
+            sent.push(button);
+        }
+        Ok(sent)
+    }
+}
 
-            let n = sum_path(output, &count_map);
-            count_map2.insert((*vec[0], *vec[1]), n);
-        });
+/// Why a line of the input can't be sent to the door
+#[derive(Debug, PartialEq)]
+enum CodeError {
+    Empty,
+    Button { code: String, button: char },
+    TooLarge { code: String },
+}
 
-        std::mem::swap(&mut count_map, &mut count_map2);
-        count_map2.clear();
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeError::Empty => write!(f, "the code is empty"),
+            CodeError::Button { code, button } => {
+                write!(f, "{code} has {button:?}, which is not on the door keypad")
+            }
+            CodeError::TooLarge { code } => {
+                write!(
+                    f,
+                    "the presses or complexity of {code} do not fit in a u128"
+                )
+            }
+        }
     }
+}
 
-    // finally, we apply the calculated move costs to the original string
-    sum_path(numpad_result, &count_map)
+#[derive(Debug, PartialEq)]
+struct Complexity {
+    presses: u128,
+    numeric: u128,
+    complexity: u128,
 }
 
-fn calculate_complexity(code: &str, robot_count: u32) -> usize {
-    // first we get an actual set of moves (like "<A^A>^^AvvvA")
-    let numpad_result = numpad_moves(code);
-    // and then we calculate how many moves we would need to perform based on
-    // the count of the intermediate robots
-    let intermediate_costs = calculate_button_press_count(numpad_result, robot_count - 1);
-    code.strip_suffix('A').unwrap().parse::<usize>().unwrap() * intermediate_costs
+/// The complexity of a code is how many presses we need to make to send it,
+/// times its numeric part. The numeric part is its digits without leading
+/// zeros, so a code that doesn't end in A is worth as much as one that does,
+/// although the robots don't press A at the end of it
+fn calculate_complexity(code: &str, chain: &Chain) -> Result<Complexity, CodeError> {
+    if code.is_empty() {
+        return Err(CodeError::Empty);
+    }
+    if let Some(button) = code
+        .chars()
+        .find(|button| !chain.keypads[0].buttons.contains_key(button))
+    {
+        let code = code.to_string();
+        return Err(CodeError::Button { code, button });
+    }
+
+    let too_large = || CodeError::TooLarge {
+        code: code.to_string(),
+    };
+    let mut numeric = 0u128;
+    for digit in code.chars().filter_map(|button| button.to_digit(10)) {
+        numeric = numeric
+            .checked_mul(10)
+            .and_then(|numeric| numeric.checked_add(digit as u128))
+            .ok_or_else(too_large)?;
+    }
+    let presses = chain.count(code).ok_or_else(too_large)?;
+    let complexity = presses.checked_mul(numeric).ok_or_else(too_large)?;
+    Ok(Complexity {
+        presses,
+        numeric,
+        complexity,
+    })
+}
+
+fn codes(input: &str) -> impl Iterator<Item = &str> {
+    input.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
+/// Our presses for each code with the robots of part 1, checked by replaying
+/// them through the robots
+pub fn show_presses(input: String, params: &Params) -> String {
+    let chain = Chain::puzzle(params.get(&ROBOTS_P1));
+    let mut output = String::new();
+    for code in codes(&input) {
+        if let Err(error) = calculate_complexity(code, &chain) {
+            output += &format!("{error}\n");
+            continue;
+        }
+        let presses = chain.presses(code);
+        let sent = chain.replay(&presses).unwrap_or_else(|gap| panic!("{gap}"));
+        assert_eq!(sent, code, "the presses for {code} send {sent} instead");
+        output += &format!("{code}: {} presses {presses}\n", presses.len());
+    }
+    output
 }
 
-pub fn part1(input: String) -> String {
-    let codes = input.lines().map(|line| line);
+/// How many presses each code takes, its numeric part and its complexity,
+/// for both parts
+pub fn complexity_report(input: String, params: &Params) -> String {
+    let mut output = String::new();
+    for (part, robots) in [(1, params.get(&ROBOTS_P1)), (2, params.get(&ROBOTS_P2))] {
+        let chain = Chain::puzzle(robots);
+        output += &format!("Part {part} with {robots} robots:\n");
+        let mut total = Some(0u128);
+        for code in codes(&input) {
+            match calculate_complexity(code, &chain) {
+                Ok(Complexity {
+                    presses,
+                    numeric,
+                    complexity,
+                }) => {
+                    output += &format!("{code}: {presses} presses * {numeric} = {complexity}\n");
+                    total = total.and_then(|total| total.checked_add(complexity));
+                }
+                Err(error) => output += &format!("{error}\n"),
+            }
+        }
+        match total {
+            Some(total) => output += &format!("Total complexity {total}\n"),
+            None => output += "The total complexity does not fit in a u128\n",
+        }
+    }
+    output
+}
 
-    codes
-        .map(|code| calculate_complexity(code, 2))
-        .sum::<usize>()
-        .to_string()
+fn total_complexity(input: &str, robots: usize) -> u128 {
+    let chain = Chain::puzzle(robots);
+    codes(input)
+        .map(|code| {
+            calculate_complexity(code, &chain)
+                .unwrap_or_else(|error| panic!("{error}"))
+                .complexity
+        })
+        .try_fold(0u128, u128::checked_add)
+        .expect("the total complexity does not fit in a u128")
 }
 
-pub fn part2(input: String) -> String {
-    let codes = input.lines().map(|line| line);
+pub fn part1(input: String, params: &Params) -> String {
+    total_complexity(&input, params.get(&ROBOTS_P1)).to_string()
+}
 
-    codes
-        .map(|code| calculate_complexity(code, 25))
-        .sum::<usize>()
-        .to_string()
+pub fn part2(input: String, params: &Params) -> String {
+    total_complexity(&input, params.get(&ROBOTS_P2)).to_string()
 }
 
 #[cfg(test)]
 mod tests {
     #[test]
     fn sample_p1() {
-        use super::part1;
+        use super::{part1, Params};
 
         let input = "029A
 980A
@@ -208,12 +416,12 @@ mod tests {
 456A
 379A"
             .to_string();
-        assert_eq!(part1(input), "126384");
+        assert_eq!(part1(input, &Params::default()), "126384");
     }
 
     #[test]
     fn sample_p2() {
-        use super::part2;
+        use super::{part2, Params};
 
         let input = "029A
 980A
@@ -221,6 +429,179 @@ mod tests {
 456A
 379A"
             .to_string();
-        assert_eq!(part2(input), "154115708116294");
+        assert_eq!(part2(input.clone(), &Params::default()), "154115708116294");
+
+        // with as many robots as part 1 it is part 1
+        let mut params = Params::default();
+        params.set("robots_p2", "2");
+        assert_eq!(part2(input, &params), "126384");
+    }
+
+    #[test]
+    fn sample_presses() {
+        use super::Chain;
+
+        // the puzzle's presses for 029A, at the door and through each robot
+        let sequences = [
+            "<A^A>^^AvvvA",
+            "v<<A>>^A<A>AvA<^AA>A<vAAA>^A",
+            "<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A",
+        ];
+        for (robots, sequence) in sequences.into_iter().enumerate() {
+            let chain = Chain::puzzle(robots);
+            assert_eq!(chain.replay(sequence), Ok("029A".to_string()));
+            let presses = chain.presses("029A");
+            assert_eq!(presses.len(), sequence.len());
+            assert_eq!(chain.replay(&presses), Ok("029A".to_string()));
+        }
+
+        let chain = Chain::puzzle(2);
+        for (code, length) in [("980A", 60), ("179A", 68), ("456A", 64), ("379A", 64)] {
+            assert_eq!(chain.presses(code).len(), length);
+        }
+
+        // the counts agree with the sequences as the chain gets longer
+        for robots in 0..5 {
+            let chain = Chain::puzzle(robots);
+            for code in ["029A", "980A", "179A", "456A", "379A"] {
+                let presses = chain.presses(code);
+                assert_eq!(chain.count(code), Some(presses.len() as u128));
+                assert_eq!(chain.replay(&presses), Ok(code.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn replaying_into_gaps() {
+        use super::{Chain, Gap};
+
+        let chain = Chain::puzzle(0);
+        assert_eq!(
+            chain.replay("<<A"),
+            Err(Gap {
+                keypad: 0,
+                press: 1
+            })
+        );
+        let chain = Chain::puzzle(1);
+        assert_eq!(
+            chain.replay("<A<<"),
+            Err(Gap {
+                keypad: 1,
+                press: 2
+            })
+        );
+        assert_eq!(chain.replay("v<<A>>^A"), Ok("0".to_string()));
+    }
+
+    #[test]
+    fn other_keypads() {
+        use super::{Chain, Gap, Keypad, DIRECTIONAL, NUMERIC};
+
+        // robots directed with upside down keypads
+        let flipped = Keypad::parse("<v>\n ^A");
+        let chain = Chain::new(vec![
+            Keypad::parse(NUMERIC),
+            flipped.clone(),
+            flipped,
+            Keypad::parse(DIRECTIONAL),
+        ]);
+        for code in ["029A", "980A", "179A", "456A", "379A"] {
+            let presses = chain.presses(code);
+            assert_eq!(chain.count(code), Some(presses.len() as u128));
+            assert_eq!(chain.replay(&presses), Ok(code.to_string()));
+        }
+
+        // a door where the arm has to zig-zag around the gaps between A and 1
+        let door = Keypad::parse("12\n345\n 6A");
+        let chain = Chain::new(vec![door.clone()]);
+        assert_eq!(
+            chain.replay("<<^^A"),
+            Err(Gap {
+                keypad: 0,
+                press: 1
+            })
+        );
+        assert_eq!(chain.replay("^<<^A"), Ok("1".to_string()));
+        let chain = Chain::new(vec![door, Keypad::parse(DIRECTIONAL)]);
+        let presses = chain.presses("1A");
+        assert_eq!(chain.count("1A"), Some(presses.len() as u128));
+        assert_eq!(chain.replay(&presses), Ok("1A".to_string()));
+    }
+
+    #[test]
+    fn complexities() {
+        use super::{
+            calculate_complexity, complexity_report, Chain, CodeError, Complexity, Params,
+        };
+
+        // the puzzle's complexities with two robots
+        let chain = Chain::puzzle(2);
+        for (code, presses, numeric) in [
+            ("029A", 68, 29),
+            ("980A", 60, 980),
+            ("179A", 68, 179),
+            ("456A", 64, 456),
+            ("379A", 64, 379),
+        ] {
+            let complexity = presses * numeric;
+            assert_eq!(
+                calculate_complexity(code, &chain),
+                Ok(Complexity {
+                    presses,
+                    numeric,
+                    complexity
+                })
+            );
+        }
+
+        // without the final A the robots stop on the 9
+        let report = calculate_complexity("029", &chain).unwrap();
+        assert_eq!(
+            (report.numeric, report.complexity),
+            (29, 29 * report.presses)
+        );
+        let presses = chain.presses("029");
+        assert_eq!(report.presses, presses.len() as u128);
+        assert_eq!(chain.replay(&presses), Ok("029".to_string()));
+        assert_eq!(calculate_complexity("000A", &chain).unwrap().complexity, 0);
+
+        assert_eq!(calculate_complexity("", &chain), Err(CodeError::Empty));
+        let code = "02B9A".to_string();
+        assert_eq!(
+            calculate_complexity(&code, &chain),
+            Err(CodeError::Button { code, button: 'B' })
+        );
+        let code = "1".repeat(40);
+        assert_eq!(
+            calculate_complexity(&code, &chain),
+            Err(CodeError::TooLarge { code })
+        );
+
+        // far more robots than a usize could count presses for
+        let chain = Chain::puzzle(80);
+        assert!(calculate_complexity("029A", &chain).unwrap().presses > u64::MAX as u128);
+        let code = "9".repeat(20) + "A";
+        assert_eq!(
+            calculate_complexity(&code, &chain),
+            Err(CodeError::TooLarge { code })
+        );
+
+        // so many robots that even a single press can't be counted, which
+        // every code reports rather than the chain failing to be built
+        let chain = Chain::puzzle(120);
+        let code = "029A".to_string();
+        assert_eq!(
+            calculate_complexity(&code, &chain),
+            Err(CodeError::TooLarge { code })
+        );
+        let mut params = Params::default();
+        params.set("robots_p2", "120");
+        let report = complexity_report("029A\n".to_string(), &params);
+        assert!(report.ends_with(
+            "Part 2 with 120 robots:\n\
+             the presses or complexity of 029A do not fit in a u128\n\
+             Total complexity 0\n"
+        ));
     }
 }
@@ -1,45 +1,127 @@
-use std::array;
-
 use bitvec::bitvec;
 
-fn process(mut monkey: u64) -> u64 {
-    monkey = ((64 * monkey) ^ monkey) % 16777216;
+use super::params::{parses, Param, Params};
 
-    monkey = (monkey ^ (monkey / 32)) % 16777216;
+const SECRETS: Param = Param {
+    key: "secrets",
+    default: "2000",
+    help: "how many new secret numbers each buyer makes in a day",
+    parse: parses::<usize>,
+};
+pub const PARAMS: &[Param] = &[SECRETS];
 
-    monkey = ((monkey * 2048) ^ monkey) % 16777216;
+// secrets are pruned to 24 bits after every step
+const PRUNE: u32 = (1 << 24) - 1;
 
-    monkey
+/// The secret number that follows another
+fn process(mut secret: u32) -> u32 {
+    // multiplying by 64 and 2048 are shifts, and pruning is a mask. the
+    // bits shifted out of a u32 would be pruned anyway
+    secret = ((secret << 6) ^ secret) & PRUNE;
+    secret ^= secret >> 5;
+    ((secret << 11) ^ secret) & PRUNE
 }
 
-pub fn part1(input: String) -> String {
-    let monkeys = input
-        .lines()
-        .map(|line| line.parse::<u64>().unwrap())
-        .collect::<Vec<_>>();
-
-    let mut total = 0;
-    const LANES: usize = 16;
-    for monkes in monkeys.chunks_exact(LANES) {
-        let mut monkes: [u64; LANES] = array::from_fn(|i| monkes[i]);
-        for _ in 0..2000 {
-            monkes = monkes
-                .map(|monkey| ((64 * monkey) ^ monkey) % 16777216)
-                .map(|monkey| (monkey ^ (monkey / 32)) % 16777216)
-                .map(|monkey| ((monkey * 2048) ^ monkey) % 16777216);
-        }
-        for monkey in monkes {
-            total += monkey;
-        }
+/// The secret number that another follows. Each step mixes in a shifted
+/// copy of the secret, which we undo by mixing in every shifted copy that
+/// still fits in 24 bits
+pub fn inverse(mut secret: u32) -> u32 {
+    assert!(secret <= PRUNE, "{secret} is not a secret number");
+    secret ^= (secret << 11) ^ (secret << 22);
+    secret &= PRUNE;
+    secret ^= (secret >> 5) ^ (secret >> 10) ^ (secret >> 15) ^ (secret >> 20);
+    secret ^= (secret << 6) ^ (secret << 12) ^ (secret << 18);
+    secret & PRUNE
+}
+
+/// Only the low 24 bits of a buyer's starting number make it through the
+/// pruning of the first step, so that is all we need to keep of it
+fn prune(start: u64) -> u32 {
+    (start & PRUNE as u64) as u32
+}
+
+/// A buyer's secret number, which as an iterator gives every secret number
+/// that follows it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Secret(u32);
+
+impl Secret {
+    pub fn new(start: u64) -> Secret {
+        Secret(prune(start))
     }
-    for monkey in monkeys.chunks_exact(LANES).remainder() {
-        let mut monkey = *monkey;
-        for _ in 0..2000 {
-            monkey = process(monkey);
+}
+
+impl Iterator for Secret {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        self.0 = process(self.0);
+        Some(self.0)
+    }
+}
+
+/// Moves every secret some number of steps ahead, taking `LANES` of them
+/// through every step before going on to the next ones. Every lane takes the
+/// same steps, so the compiler does several lanes at once with SIMD, and with
+/// enough lanes they don't have to wait on each other's results
+pub fn process_batch<const LANES: usize>(secrets: &mut [u32], steps: usize) {
+    for chunk in secrets.chunks_mut(LANES) {
+        for _ in 0..steps {
+            for secret in chunk.iter_mut() {
+                *secret = process(*secret);
+            }
         }
-        total += monkey;
     }
-    total.to_string()
+}
+
+fn parse_input(input: &str) -> Vec<u64> {
+    input
+        .lines()
+        .map(|line| {
+            line.trim()
+                .parse::<u64>()
+                .unwrap_or_else(|e| panic!("{line} is not a starting number: {e}"))
+        })
+        .collect()
+}
+
+// how many secrets part 1 moves ahead together, which is as fast as any
+// larger number and faster than a few vectors' worth
+const LANES: usize = 256;
+
+pub fn part1(input: String, params: &Params) -> String {
+    let starts = parse_input(&input);
+    let steps = params.get(&SECRETS);
+    if steps == 0 {
+        // nothing has been pruned yet
+        return starts.into_iter().map(u128::from).sum::<u128>().to_string();
+    }
+    let mut secrets = starts.into_iter().map(prune).collect::<Vec<_>>();
+    process_batch::<LANES>(&mut secrets, steps);
+    secrets
+        .into_iter()
+        .map(|secret| secret as u64)
+        .sum::<u64>()
+        .to_string()
+}
+
+/// The secret numbers that turn into each of the given ones after as many
+/// steps as a buyer takes in a day, which makes buyers with known answers
+pub fn rewind(input: String, params: &Params) -> Vec<u32> {
+    let steps = params.get::<usize>(&SECRETS);
+    parse_input(&input)
+        .into_iter()
+        .map(|secret| match u32::try_from(secret) {
+            // rewinding can only give secret numbers, so only those can be
+            // rewound to
+            Ok(secret) if secret <= PRUNE => secret,
+            _ => panic!(
+                "{secret} is not a secret number, which are less than {}",
+                PRUNE + 1
+            ),
+        })
+        .map(|secret| (0..steps).fold(secret, |secret, _| inverse(secret)))
+        .collect()
 }
 
 fn index(deltas: (i8, i8, i8, i8)) -> usize {
@@ -49,23 +131,23 @@ fn index(deltas: (i8, i8, i8, i8)) -> usize {
         + (deltas.3 + 9) as usize
 }
 
-pub fn part2(input: String) -> String {
-    let monkeys = input.lines().map(|line| line.parse::<u64>().unwrap());
+pub fn part2(input: String, params: &Params) -> String {
+    let monkeys = parse_input(&input);
 
     // map from 4 deltas to the total amount of bananas it buys
     // we use vectors with indexes based on the delta sequenes, as while
     // there are a lot of possible combinations, the total amount
     // is still relatively low and clearly outperforms fxhashmap
+    let secrets = params.get::<usize>(&SECRETS);
     let mut map = vec![0; 19usize.pow(4)];
-    for mut monkey in monkeys {
+    for monkey in monkeys {
         // we want to avoid checking delta sequences multiple times as the
         // monkey buys the first one that matches
         let mut seen = bitvec![0; 19usize.pow(4)];
         let mut old_price = (monkey % 10) as i8;
         let mut deltas = vec![];
 
-        for _ in 0..2000 {
-            monkey = process(monkey);
+        for monkey in Secret::new(monkey).take(secrets) {
             let price = (monkey % 10) as i8;
 
             let delta = price - old_price;
@@ -95,25 +177,130 @@ pub fn part2(input: String) -> String {
 mod tests {
     #[test]
     fn sample_p1() {
-        use super::part1;
+        use super::{part1, Params};
 
         let input = "1
 10
 100
 2024"
             .to_string();
-        assert_eq!(part1(input), "37327623");
+        assert_eq!(part1(input, &Params::default()), "37327623");
     }
 
     #[test]
     fn sample_p2() {
-        use super::part2;
+        use super::{part2, Params};
 
         let input = "1
 2
 3
 2024"
             .to_string();
-        assert_eq!(part2(input), "23");
+        assert_eq!(part2(input, &Params::default()), "23");
+    }
+
+    #[test]
+    fn fewer_secrets() {
+        use super::{part1, part2, Params};
+
+        // the puzzle's example of the next ten secrets of a buyer starting at 123
+        let mut params = Params::default();
+        params.set("secrets", "1");
+        assert_eq!(part1("123".to_string(), &params), "15887950");
+        params.set("secrets", "10");
+        assert_eq!(part1("123".to_string(), &params), "5908254");
+        assert_eq!(part2("123".to_string(), &params), "6");
+    }
+
+    #[test]
+    fn secret_numbers() {
+        use super::{inverse, process, Secret, PRUNE};
+
+        // the puzzle's example of the next ten secrets of a buyer starting at 123
+        let secrets = [
+            15887950, 16495136, 527345, 704524, 1553684, 12683156, 11100544, 12249484, 7753432,
+            5908254,
+        ];
+        assert!(Secret::new(123).take(10).eq(secrets));
+
+        // and back again
+        let mut secret = 5908254;
+        for &previous in secrets.iter().rev().skip(1).chain([&123]) {
+            secret = inverse(secret);
+            assert_eq!(secret, previous);
+        }
+        for secret in (0..=PRUNE).step_by(997).chain([PRUNE]) {
+            assert_eq!(inverse(process(secret)), secret);
+            assert_eq!(process(inverse(secret)), secret);
+        }
+    }
+
+    #[test]
+    fn batches() {
+        use super::{process_batch, Secret};
+
+        let start = (0..1000).map(|i| i * 16411).collect::<Vec<u32>>();
+        let expected = start
+            .iter()
+            .map(|&secret| Secret::new(secret.into()).nth(99).unwrap())
+            .collect::<Vec<_>>();
+        for lanes in [1, 7, 16, 256, 4096] {
+            let mut secrets = start.clone();
+            match lanes {
+                1 => process_batch::<1>(&mut secrets, 100),
+                7 => process_batch::<7>(&mut secrets, 100),
+                16 => process_batch::<16>(&mut secrets, 100),
+                256 => process_batch::<256>(&mut secrets, 100),
+                _ => process_batch::<4096>(&mut secrets, 100),
+            }
+            assert_eq!(secrets, expected, "{lanes} lanes");
+        }
+    }
+
+    #[test]
+    fn rewound_buyers() {
+        use super::{part1, rewind, Params};
+
+        // buyers that end the day on these secrets add up to their sum
+        let ends = [1u32, 2, 3, 16777215, 8685429];
+        let input = ends.map(|secret| secret.to_string()).join("\n");
+        let params = Params::default();
+        let starts = rewind(input, &params);
+        let input = starts
+            .iter()
+            .map(|secret| secret.to_string())
+            .collect::<Vec<_>>();
+        let total = ends.iter().map(|&secret| secret as u64).sum::<u64>();
+        assert_eq!(part1(input.join("\n"), &params), total.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "16777216 is not a secret number")]
+    fn too_large() {
+        use super::{rewind, Params};
+
+        rewind("1\n16777216".to_string(), &Params::default());
+    }
+
+    #[test]
+    fn large_starts() {
+        use super::{part1, part2, Params};
+
+        // the first step prunes starting numbers down to their low 24 bits,
+        // but the first price still comes from the whole number
+        let mut params = Params::default();
+        params.set("secrets", "10");
+        let large = (u64::MAX - 16777215 + 123).to_string();
+        assert_eq!(part1(large.clone(), &params), "5908254");
+        assert_eq!(part1((123 + 16777216).to_string(), &params), "5908254");
+        assert_eq!(part2("16777339".to_string(), &params), "6");
+        assert_eq!(part2(large.clone(), &params), "6");
+        // without any steps the whole numbers add up, past what a u64 holds
+        params.set("secrets", "0");
+        let total = 2 * large.parse::<u128>().unwrap();
+        assert_eq!(
+            part1(format!("{large}\n{large}"), &params),
+            total.to_string()
+        );
     }
 }
@@ -1,80 +1,116 @@
+use std::fmt;
+
+use num_bigint::BigUint;
 use regex::Regex;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::util::XorShift;
 
-fn execute_op(lhs: u8, rhs: u8, op: &str) -> u8 {
+fn execute_op(lhs: u64, rhs: u64, op: &str) -> u64 {
     match op {
         "AND" => lhs & rhs,
         "OR" => lhs | rhs,
         "XOR" => lhs ^ rhs,
+        "NAND" => !(lhs & rhs),
+        "NOR" => !(lhs | rhs),
+        "XNOR" => !(lhs ^ rhs),
+        // NOT gates are stored with the same wire on both sides
+        "NOT" => !lhs,
         _ => unreachable!(),
     }
 }
 fn parse_input(input: &str) -> (&str, Vec<[&str; 4]>) {
     let (wire_values, gate_connections) = input.split_once("\n\n").unwrap();
-    let gate_re = Regex::new(r"(.{3}) (AND|OR|XOR) (.{3}) -> (.{3})").unwrap();
+    let gate_re = Regex::new(r"(.{3}) (AND|OR|XOR|NAND|NOR|XNOR) (.{3}) -> (.{3})").unwrap();
+    let not_re = Regex::new(r"(NOT) (.{3}) -> (.{3})").unwrap();
     let gate_connections = gate_connections
         .lines()
         .map(|line| {
-            let caps = gate_re.captures(line).unwrap();
+            // a NOT gate only has a single input, which we simply use for both
+            // sides so that every gate has the same shape
+            if let Some(caps) = not_re.captures(line) {
+                let (_, [op, input, ret]) = caps.extract();
+                return [input, op, input, ret];
+            }
+            let caps = gate_re
+                .captures(line)
+                .unwrap_or_else(|| panic!("could not parse gate {line}"));
             let (_, s) = caps.extract::<4>();
             s
         })
         .collect::<Vec<_>>();
     (wire_values, gate_connections)
 }
-pub fn part1(input: String) -> String {
-    let (wire_values, mut gate_connections) = parse_input(&input);
-    let mut wire_map = FxHashMap::default();
-    wire_values.lines().for_each(|line| {
-        let (name, value) = line.split_once(": ").unwrap();
-        wire_map.insert(name, value.parse::<u8>().unwrap());
-    });
-
-    // loop while removing connections until they have all been applied
-    while !gate_connections.is_empty() {
-        gate_connections.retain(|&[lhs, op, rhs, ret]| {
-            let lhs_val = wire_map.get(lhs);
-            let rhs_val = wire_map.get(rhs);
-            match (lhs_val, rhs_val) {
-                (Some(&a), Some(&b)) => {
-                    wire_map.insert(ret, execute_op(a, b, op));
-                    false
-                }
-                _ => true,
+
+#[derive(Debug, PartialEq)]
+enum CircuitError {
+    UndrivenWires(Vec<String>),
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::UndrivenWires(wires) => {
+                write!(f, "wires are used but never driven: {}", wires.join(", "))
             }
-        });
+            CircuitError::Cycle(wires) => {
+                write!(
+                    f,
+                    "gates form a loop: {} -> {}",
+                    wires.join(" -> "),
+                    wires[0]
+                )
+            }
+        }
     }
+}
 
-    let mut out = 0u64;
-    for i in 0..64 {
-        let s = format!("z{i:02}");
-        if let Some(&val) = wire_map.get(&s.as_str()) {
-            out |= (val as u64) << i
-        } else {
-            break;
-        }
+pub fn part1(input: String) -> String {
+    let (wire_values, gate_connections) = parse_input(&input);
+    let circuit = Circuit::new(&gate_connections);
+    let values = circuit
+        .evaluate(wire_values)
+        .unwrap_or_else(|e| panic!("could not evaluate circuit: {e}"));
+
+    // the output can be any number of bits wide
+    let mut out = BigUint::ZERO;
+    for &wire in circuit.z.iter() {
+        let bit = circuit.names[wire][1..].parse::<u64>().unwrap();
+        out.set_bit(bit, values[wire] & 1 != 0);
     }
 
     out.to_string()
 }
 
-pub fn part2(input: String) -> String {
-    let (_, gate_connections) = parse_input(&input);
+fn bit_wires<'a>(wires: impl Iterator<Item = &'a str>, prefix: char) -> Vec<&'a str> {
+    // returns wires like x00, x01, ... ordered by their bit
+    let mut wires = wires
+        .filter(|wire| wire.starts_with(prefix) && wire[1..].chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>();
+    wires.sort_by_key(|wire| wire[1..].parse::<usize>().unwrap());
+    wires.dedup();
+    wires
+}
+
+fn suspicious_outputs<'a>(gate_connections: &[[&'a str; 4]]) -> Vec<&'a str> {
     let mut wire_map: FxHashMap<&str, Vec<(&str, &str)>> = FxHashMap::default();
 
     // we need a map to know what operations follow another operation
     for &[lhs, op, rhs, ret] in gate_connections.iter() {
-        wire_map.entry(lhs).or_insert(vec![]).push((op, ret));
-        wire_map.entry(rhs).or_insert(vec![]).push((op, ret));
+        wire_map.entry(lhs).or_default().push((op, ret));
+        wire_map.entry(rhs).or_default().push((op, ret));
     }
+    let last_bit = bit_wires(gate_connections.iter().map(|gate| gate[3]), 'z')
+        .pop()
+        .unwrap_or_default();
 
     let mut wrong_outputs = vec![];
     for &[lhs, op, rhs, ret] in gate_connections.iter() {
         // basically we ensure the adder looks like this:
         // https://en.wikipedia.org/wiki/Adder_(electronics)#/media/File:Fulladder.gif
         let chained_ops = wire_map.get(&ret);
-        let chained_ops_contain =
-            |op| chained_ops.is_some_and(|v| v.iter().find(|a| a.0 == op).is_some());
+        let chained_ops_contain = |op| chained_ops.is_some_and(|v| v.iter().any(|a| a.0 == op));
 
         let has_chained_xor = chained_ops_contain("XOR");
         let has_chained_and = chained_ops_contain("AND");
@@ -83,52 +119,527 @@ pub fn part2(input: String) -> String {
         let takes_input_bit = (lhs.starts_with('x') && rhs.starts_with('y'))
             || (rhs.starts_with('x') && lhs.starts_with('y'));
         let outputs_bit = ret.starts_with('z');
-        let outputs_last_bit = ret == "z45";
+        let outputs_last_bit = ret == last_bit;
 
         let valid = match op {
+            // XOR only outputs a bit if it doesn't take an input bit, and
+            // only takes an input bit if a XOR follows it, unless the input
+            // bits are the first bits (no carryover bit exists)
             "XOR" => {
-                // XOR only outputs a bit if it doesn't take an input bit
-                if !takes_input_bit && outputs_bit {
-                    true
-                // XOR only takes an input bit if a XOR follows it
-                } else if takes_input_bit && has_chained_xor {
-                    true
-                // unless the input bits are the first bits (no carryover bit exists)
-                } else if takes_first_input && outputs_bit {
-                    true
-                } else {
-                    false
+                (!takes_input_bit && outputs_bit)
+                    || (takes_input_bit && has_chained_xor)
+                    || (takes_first_input && outputs_bit)
+            }
+            // OR either outputs into the last bit or an AND and XOR (carryover bit)
+            "OR" => outputs_last_bit || (has_chained_and && has_chained_xor),
+            // ANDs only lead into ORs, unless the input bits are the first bits
+            "AND" => has_chained_or || takes_first_input,
+            // any other gate doesn't belong in an adder built like the above
+            _ => false,
+        };
+        if !valid {
+            wrong_outputs.push(ret);
+        }
+    }
+    wrong_outputs
+}
+
+// the gate list with wires replaced by indexes, so that we can cheaply
+// simulate it with different output wires swapped around
+struct Circuit<'a> {
+    names: Vec<&'a str>,
+    x: Vec<usize>,
+    y: Vec<usize>,
+    z: Vec<usize>,
+    gates: Vec<(usize, &'a str, usize)>,
+    outputs: Vec<usize>,
+    // test operands, where every bit of a word is a separate addition. each
+    // block holds the words for x, y and the expected sum per bit
+    blocks: Vec<(Vec<u64>, Vec<u64>, Vec<u64>)>,
+}
+
+impl<'a> Circuit<'a> {
+    const RANDOM_BLOCKS: usize = 4;
+
+    fn new(gate_connections: &[[&'a str; 4]]) -> Self {
+        let mut ids: FxHashMap<&str, usize> = FxHashMap::default();
+        let mut names = vec![];
+        let mut id = |wire: &'a str| {
+            *ids.entry(wire).or_insert_with(|| {
+                names.push(wire);
+                names.len() - 1
+            })
+        };
+        let mut gates = vec![];
+        let mut outputs = vec![];
+        for &[lhs, op, rhs, ret] in gate_connections {
+            gates.push((id(lhs), op, id(rhs)));
+            outputs.push(id(ret));
+        }
+        let ids_of = |wires: Vec<&str>| wires.into_iter().map(|w| ids[w]).collect::<Vec<_>>();
+        let x = ids_of(bit_wires(names.iter().copied(), 'x'));
+        let y = ids_of(bit_wires(names.iter().copied(), 'y'));
+        let z = ids_of(bit_wires(names.iter().copied(), 'z'));
+
+        Circuit {
+            names,
+            x,
+            y,
+            z,
+            gates,
+            outputs,
+            blocks: vec![],
+        }
+    }
+
+    fn generate_operands(&mut self) {
+        assert_eq!(
+            self.x.len(),
+            self.y.len(),
+            "x and y inputs should have the same width"
+        );
+        let width = self.x.len();
+        let mut operands: Vec<(Vec<bool>, Vec<bool>)> = vec![];
+
+        // every combination of the input bits and an incoming carry for each bit
+        for bit in 0..width {
+            for combination in 0..8 {
+                let mut x = vec![false; width];
+                let mut y = vec![false; width];
+                x[bit] = combination & 1 != 0;
+                y[bit] = combination & 2 != 0;
+                if bit > 0 {
+                    x[bit - 1] = combination & 4 != 0;
+                    y[bit - 1] = combination & 4 != 0;
                 }
+                operands.push((x, y));
             }
-            "OR" => {
-                // OR either outputs into z45 or an AND and XOR (carryover bit)
-                if outputs_last_bit || (has_chained_and && has_chained_xor) {
-                    true
-                } else {
-                    false
+        }
+        // carries that ripple through the whole adder
+        let ones = vec![true; width];
+        let zeros = vec![false; width];
+        let one = (0..width).map(|bit| bit == 0).collect::<Vec<_>>();
+        let evens = (0..width).map(|bit| bit % 2 == 0).collect::<Vec<_>>();
+        let odds = (0..width).map(|bit| bit % 2 == 1).collect::<Vec<_>>();
+        operands.push((ones.clone(), one.clone()));
+        operands.push((one, ones.clone()));
+        operands.push((ones.clone(), ones.clone()));
+        operands.push((ones.clone(), zeros.clone()));
+        operands.push((zeros.clone(), zeros));
+        operands.push((evens.clone(), odds.clone()));
+        operands.push((odds, evens));
+
+        let mut rng = XorShift::new(0x2024_1224);
+        for _ in 0..64 * Self::RANDOM_BLOCKS {
+            let x = (0..width).map(|_| rng.below(2) != 0).collect();
+            let y = (0..width).map(|_| rng.below(2) != 0).collect();
+            operands.push((x, y));
+        }
+
+        self.blocks = operands
+            .chunks(64)
+            .map(|chunk| {
+                let mut x = vec![0u64; width];
+                let mut y = vec![0u64; width];
+                for (lane, (x_bits, y_bits)) in chunk.iter().enumerate() {
+                    for bit in 0..width {
+                        x[bit] |= (x_bits[bit] as u64) << lane;
+                        y[bit] |= (y_bits[bit] as u64) << lane;
+                    }
+                }
+                // ripple carry addition for all lanes at once. any extra
+                // output bits past the carry should stay zero
+                let mut sum = vec![0u64; self.z.len().max(width + 1)];
+                let mut carry = 0;
+                for bit in 0..width {
+                    sum[bit] = x[bit] ^ y[bit] ^ carry;
+                    carry = (x[bit] & y[bit]) | (carry & (x[bit] ^ y[bit]));
+                }
+                sum[width] = carry;
+                (x, y, sum)
+            })
+            .collect();
+    }
+
+    fn topological_order(&self, outputs: &[usize]) -> Result<Vec<usize>, Vec<usize>> {
+        // kahn's algorithm over the gates. a swap can create a loop, in which
+        // case the circuit can't be evaluated and we return the gates that
+        // could not be ordered instead
+        let mut driver = vec![usize::MAX; self.names.len()];
+        for (gate, &wire) in outputs.iter().enumerate() {
+            driver[wire] = gate;
+        }
+        let mut in_degree = vec![0; self.gates.len()];
+        let mut dependents = vec![vec![]; self.gates.len()];
+        for (gate, &(lhs, _, rhs)) in self.gates.iter().enumerate() {
+            for input in [lhs, rhs] {
+                if driver[input] != usize::MAX {
+                    in_degree[gate] += 1;
+                    dependents[driver[input]].push(gate);
                 }
             }
-            "AND" => {
-                // ANDs only lead into ORs
-                if has_chained_or {
-                    true
-                // unless the input bits are the first bits (no carryover bit exists)
-                } else if takes_first_input {
-                    true
-                } else {
-                    false
+        }
+
+        let mut order = (0..self.gates.len())
+            .filter(|&gate| in_degree[gate] == 0)
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        while let Some(&gate) = order.get(i) {
+            for &dependent in dependents[gate].iter() {
+                in_degree[dependent] -= 1;
+                if in_degree[dependent] == 0 {
+                    order.push(dependent);
                 }
             }
-            _ => {
-                unreachable!()
+            i += 1;
+        }
+        if order.len() == self.gates.len() {
+            Ok(order)
+        } else {
+            Err((0..self.gates.len())
+                .filter(|&gate| in_degree[gate] > 0)
+                .collect())
+        }
+    }
+
+    fn find_cycle(&self, remaining: &[usize]) -> Vec<&'a str> {
+        // every gate left over from the topological sort has an input driven
+        // by another left over gate, so following those inputs from any of
+        // them has to end up in a loop eventually
+        let mut driver = FxHashMap::default();
+        for &gate in remaining {
+            driver.insert(self.outputs[gate], gate);
+        }
+        let mut path = vec![];
+        let mut position = FxHashMap::default();
+        let mut gate = remaining[0];
+        while !position.contains_key(&gate) {
+            position.insert(gate, path.len());
+            path.push(gate);
+            let (lhs, _, rhs) = self.gates[gate];
+            gate = driver.get(&lhs).or(driver.get(&rhs)).copied().unwrap();
+        }
+        // we walked against the direction of the signal
+        path[position[&gate]..]
+            .iter()
+            .rev()
+            .map(|&gate| self.names[self.outputs[gate]])
+            .collect()
+    }
+
+    fn evaluate(&self, wire_values: &str) -> Result<Vec<u64>, CircuitError> {
+        let mut values = vec![0u64; self.names.len()];
+        let mut driven = FxHashSet::default();
+        for line in wire_values.lines() {
+            let (name, value) = line.split_once(": ").unwrap();
+            if let Some(wire) = self.names.iter().position(|&it| it == name) {
+                values[wire] = value.parse().unwrap();
+                driven.insert(wire);
             }
+        }
+        driven.extend(self.outputs.iter().copied());
+
+        let mut undriven = self
+            .gates
+            .iter()
+            .flat_map(|&(lhs, _, rhs)| [lhs, rhs])
+            .filter(|wire| !driven.contains(wire))
+            .map(|wire| self.names[wire].to_string())
+            .collect::<Vec<_>>();
+        if !undriven.is_empty() {
+            undriven.sort();
+            undriven.dedup();
+            return Err(CircuitError::UndrivenWires(undriven));
+        }
+
+        let order = self.topological_order(&self.outputs).map_err(|remaining| {
+            let wires = self.find_cycle(&remaining);
+            CircuitError::Cycle(wires.into_iter().map(str::to_string).collect())
+        })?;
+        for gate in order {
+            let (lhs, op, rhs) = self.gates[gate];
+            // only the lowest bit matters as the wires are single bits here
+            values[self.outputs[gate]] = execute_op(values[lhs], values[rhs], op) & 1;
+        }
+        Ok(values)
+    }
+
+    fn failures(&self, outputs: &[usize]) -> (usize, usize) {
+        // returns the lowest output bit that differs from the expected sum in
+        // any test, or the output width if the circuit adds correctly, along
+        // with how many output bits are wrong over all the tests
+        let Ok(order) = self.topological_order(outputs) else {
+            return (0, usize::MAX);
         };
-        if !valid {
-            wrong_outputs.push(ret);
+        let mut first_failing = self.z.len();
+        let mut wrong = 0;
+        let mut values = vec![0u64; self.names.len()];
+        for (x, y, sum) in self.blocks.iter() {
+            for bit in 0..self.x.len() {
+                values[self.x[bit]] = x[bit];
+                values[self.y[bit]] = y[bit];
+            }
+            for &gate in order.iter() {
+                let (lhs, op, rhs) = self.gates[gate];
+                values[outputs[gate]] = execute_op(values[lhs], values[rhs], op);
+            }
+            for bit in 0..self.z.len() {
+                let lanes = (values[self.z[bit]] ^ sum[bit]).count_ones() as usize;
+                if lanes > 0 {
+                    first_failing = first_failing.min(bit);
+                    wrong += lanes;
+                }
+            }
+        }
+        (first_failing, wrong)
+    }
+
+    fn input_levels(&self, outputs: &[usize]) -> Vec<usize> {
+        // the highest input bit that each gate depends on. in a ripple carry
+        // adder the gates of a single bit share this, so it tells us which
+        // gates are close to a failing bit
+        let mut levels = vec![0; self.names.len()];
+        for bit in 0..self.x.len() {
+            levels[self.x[bit]] = bit;
+            levels[self.y[bit]] = bit;
+        }
+        let order = self
+            .topological_order(outputs)
+            .unwrap_or_else(|_| (0..self.gates.len()).collect());
+        for gate in order {
+            let (lhs, _, rhs) = self.gates[gate];
+            levels[outputs[gate]] = levels[lhs].max(levels[rhs]);
+        }
+        outputs.iter().map(|&wire| levels[wire]).collect()
+    }
+
+    fn candidate_swaps(&self, outputs: &[usize], bit: usize, radius: usize) -> Vec<(usize, usize)> {
+        let levels = self.input_levels(outputs);
+        let near = (0..self.gates.len())
+            .filter(|&gate| levels[gate].abs_diff(bit) <= radius || outputs[gate] == self.z[bit])
+            .collect::<Vec<_>>();
+
+        // try the outputs that break the full adder structure first
+        let gate_connections = (0..self.gates.len())
+            .map(|gate| {
+                let (lhs, op, rhs) = self.gates[gate];
+                [
+                    self.names[lhs],
+                    op,
+                    self.names[rhs],
+                    self.names[outputs[gate]],
+                ]
+            })
+            .collect::<Vec<_>>();
+        let suspicious = suspicious_outputs(&gate_connections);
+        let is_suspicious = |gate: usize| suspicious.contains(&self.names[outputs[gate]]);
+
+        let mut pairs = vec![];
+        for (i, &a) in near.iter().enumerate() {
+            for &b in near[i + 1..].iter() {
+                pairs.push((a, b));
+            }
+        }
+        pairs.sort_by_key(|&(a, b)| !is_suspicious(a) as u8 + !is_suspicious(b) as u8);
+        pairs
+    }
+
+    fn search_swaps(
+        &self,
+        outputs: &mut Vec<usize>,
+        swaps: &mut Vec<(usize, usize)>,
+        max_swaps: usize,
+        radius: usize,
+    ) -> bool {
+        // depth first search where every swap has to move the first failing
+        // bit further up, or get fewer output bits wrong without moving it
+        // down. that keeps the search local to the broken bit, while still
+        // finding swaps that only fix a bit together. every swap of the answer
+        // still has to get fewer bits wrong on its own at some point
+        let (bit, wrong) = self.failures(outputs);
+        if wrong == 0 {
+            return true;
+        }
+        if swaps.len() == max_swaps {
+            return false;
+        }
+        for (a, b) in self.candidate_swaps(outputs, bit, radius) {
+            // every output is swapped at most once
+            if swaps
+                .iter()
+                .any(|&(c, d)| [c, d].contains(&a) || [c, d].contains(&b))
+            {
+                continue;
+            }
+            outputs.swap(a, b);
+            let (new_bit, new_wrong) = self.failures(outputs);
+            if new_bit > bit || (new_bit == bit && new_wrong < wrong) {
+                swaps.push((a, b));
+                if self.search_swaps(outputs, swaps, max_swaps, radius) {
+                    return true;
+                }
+                swaps.pop();
+            }
+            outputs.swap(a, b);
         }
+        false
     }
 
-    wrong_outputs.join(",").to_string()
+    fn find_swaps(&self, max_swaps: usize) -> Option<Vec<(&'a str, &'a str)>> {
+        // first look at gates around the failing bit, and only if that fails
+        // consider swapping any pair of gates
+        for radius in [1, self.x.len()] {
+            let mut outputs = self.outputs.clone();
+            let mut swaps = vec![];
+            if self.search_swaps(&mut outputs, &mut swaps, max_swaps, radius) {
+                return Some(
+                    swaps
+                        .into_iter()
+                        .map(|(a, b)| {
+                            let (a, b) = (self.names[self.outputs[a]], self.names[self.outputs[b]]);
+                            (a.min(b), a.max(b))
+                        })
+                        .collect(),
+                );
+            }
+        }
+        None
+    }
+}
+
+pub fn part2(input: String) -> String {
+    let (_, gate_connections) = parse_input(&input);
+    let mut circuit = Circuit::new(&gate_connections);
+    circuit.generate_operands();
+
+    let swaps = circuit.find_swaps(4).expect(
+        "no four output swaps that each get fewer bits wrong make the circuit add correctly",
+    );
+
+    let mut wires = swaps
+        .into_iter()
+        .flat_map(|(a, b)| [a, b])
+        .collect::<Vec<_>>();
+    wires.sort();
+    wires.join(",")
+}
+
+fn gate_colour(op: &str) -> &'static str {
+    match op {
+        "AND" => "lightblue",
+        "OR" => "palegreen",
+        "XOR" => "gold",
+        "NAND" => "steelblue",
+        "NOR" => "seagreen",
+        "XNOR" => "orange",
+        "NOT" => "lightgrey",
+        _ => unreachable!(),
+    }
+}
+
+pub fn to_dot(input: &str) -> String {
+    // gates are named after the wire they output to, so the edges can simply
+    // go from wire to wire
+    let (_, gate_connections) = parse_input(input);
+    let suspicious = suspicious_outputs(&gate_connections);
+    let inputs = gate_connections
+        .iter()
+        .flat_map(|&[lhs, _, rhs, _]| [lhs, rhs])
+        .collect::<Vec<_>>();
+
+    let mut dot = String::from("digraph circuit {\n    rankdir=LR;\n");
+    for prefix in ['x', 'y'] {
+        for wire in bit_wires(inputs.iter().copied(), prefix) {
+            dot += &format!("    \"{wire}\" [shape=circle];\n");
+        }
+    }
+    for &[_, op, _, ret] in gate_connections.iter() {
+        let mut attributes = format!(
+            "shape=box, style=filled, fillcolor={}, label=\"{op}\\n{ret}\"",
+            gate_colour(op)
+        );
+        if ret.starts_with('z') {
+            attributes += ", peripheries=2";
+        }
+        if suspicious.contains(&ret) {
+            attributes += ", color=red, penwidth=3";
+        }
+        dot += &format!("    \"{ret}\" [{attributes}];\n");
+    }
+    for &[lhs, op, rhs, ret] in gate_connections.iter() {
+        let inputs = if op == "NOT" {
+            &[lhs][..]
+        } else {
+            &[lhs, rhs][..]
+        };
+        for &wire in inputs {
+            if suspicious.contains(&wire) {
+                dot += &format!("    \"{wire}\" -> \"{ret}\" [color=red];\n");
+            } else {
+                dot += &format!("    \"{wire}\" -> \"{ret}\";\n");
+            }
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+fn verilog_name(wire: &str) -> String {
+    // bit wires become indexes into the ports, and everything else gets a
+    // prefix so that wires like "and" or "or1" can't clash with keywords
+    let is_bit_wire = |prefix| bit_wires([wire].into_iter(), prefix).len() == 1;
+    if let Some(prefix) = ['x', 'y', 'z'].into_iter().find(|&p| is_bit_wire(p)) {
+        format!("{prefix}[{}]", wire[1..].parse::<usize>().unwrap())
+    } else if wire.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        format!("w_{wire}")
+    } else {
+        // escaped identifiers end in whitespace
+        format!("\\{wire} ")
+    }
+}
+
+pub fn to_verilog(input: &str) -> String {
+    let (_, gate_connections) = parse_input(input);
+    let inputs = gate_connections
+        .iter()
+        .flat_map(|&[lhs, _, rhs, _]| [lhs, rhs])
+        .collect::<Vec<_>>();
+    let outputs = gate_connections.iter().map(|gate| gate[3]);
+    let width = |wires: Vec<&str>| {
+        wires
+            .last()
+            .map_or(0, |wire| wire[1..].parse::<usize>().unwrap() + 1)
+    };
+    let x_width = width(bit_wires(inputs.iter().copied(), 'x'));
+    let y_width = width(bit_wires(inputs.iter().copied(), 'y'));
+    let z_width = width(bit_wires(outputs.clone(), 'z'));
+
+    let mut verilog = String::from("module circuit (\n");
+    verilog += &format!("    input wire [{}:0] x,\n", x_width.max(1) - 1);
+    verilog += &format!("    input wire [{}:0] y,\n", y_width.max(1) - 1);
+    verilog += &format!("    output wire [{}:0] z\n", z_width.max(1) - 1);
+    verilog += ");\n";
+    for wire in outputs.filter(|wire| !verilog_name(wire).starts_with('z')) {
+        verilog += &format!("    wire {};\n", verilog_name(wire));
+    }
+    verilog += "\n";
+    for (i, &[lhs, op, rhs, ret]) in gate_connections.iter().enumerate() {
+        // the gate primitives take the output as their first terminal
+        if op == "NOT" {
+            let (ret, lhs) = (verilog_name(ret), verilog_name(lhs));
+            verilog += &format!("    not g{i} ({ret}, {lhs});\n");
+            continue;
+        }
+        verilog += &format!(
+            "    {} g{i} ({}, {}, {});\n",
+            op.to_lowercase(),
+            verilog_name(ret),
+            verilog_name(lhs),
+            verilog_name(rhs)
+        );
+    }
+    verilog += "endmodule\n";
+    verilog
 }
 
 #[cfg(test)]
@@ -230,4 +741,243 @@ tnw OR pbm -> gnj"
         //             .to_string();
         //         assert_eq!(part2(input), "z00,z01,z02,z05");
     }
+
+    fn ripple_carry_adder(width: usize, swaps: &[(&str, &str)]) -> String {
+        // builds an input in the puzzle format with the same gate layout as
+        // the real inputs, with the given output wires swapped
+        let mut counter = 0;
+        let mut wire = || {
+            counter += 1;
+            let letter = |n: usize| (b'a' + (n % 23) as u8) as char;
+            format!(
+                "{}{}{}",
+                letter(counter / 529),
+                letter(counter / 23),
+                letter(counter)
+            )
+        };
+        let mut gates = vec![];
+        let mut carry = wire();
+        gates.push("x00 XOR y00 -> z00".to_string());
+        gates.push(format!("x00 AND y00 -> {carry}"));
+        for bit in 1..width {
+            let (half_sum, half_carry, carry_carry) = (wire(), wire(), wire());
+            let next_carry = if bit == width - 1 {
+                format!("z{width:02}")
+            } else {
+                wire()
+            };
+            gates.push(format!("x{bit:02} XOR y{bit:02} -> {half_sum}"));
+            gates.push(format!("y{bit:02} AND x{bit:02} -> {half_carry}"));
+            gates.push(format!("{carry} XOR {half_sum} -> z{bit:02}"));
+            gates.push(format!("{half_sum} AND {carry} -> {carry_carry}"));
+            gates.push(format!("{half_carry} OR {carry_carry} -> {next_carry}"));
+            carry = next_carry;
+        }
+        let gates = gates
+            .into_iter()
+            .map(|gate| {
+                let (lhs, out) = gate.split_once(" -> ").unwrap();
+                match swaps.iter().find(|&&(a, b)| out == a || out == b) {
+                    Some(&(a, b)) => format!("{lhs} -> {}", if out == a { b } else { a }),
+                    None => gate,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut values = vec![];
+        for bit in 0..width {
+            values.push(format!("x{bit:02}: {}", bit % 2));
+            values.push(format!("y{bit:02}: {}", bit % 3 % 2));
+        }
+        format!("{}\n\n{}", values.join("\n"), gates.join("\n"))
+    }
+
+    #[test]
+    fn generated_adder_p2() {
+        use super::{part1, part2, BigUint};
+
+        let input = ripple_carry_adder(45, &[]);
+        let x = (0..45).map(|bit| (bit % 2) << bit).sum::<u64>();
+        let y = (0..45).map(|bit| (bit % 3 % 2) << bit).sum::<u64>();
+        assert_eq!(part1(input), (x + y).to_string());
+
+        // the swap kinds seen in real inputs: an output bit with the carry, a
+        // half sum with a half carry, and an output bit with a half sum
+        let swaps = [
+            ("z07", "abg"),
+            ("abh", "abi"),
+            ("z23", "aea"),
+            ("z31", "afk"),
+        ];
+        let input = ripple_carry_adder(45, &swaps);
+        assert_eq!(part2(input), "abg,abh,abi,aea,afk,z07,z23,z31");
+
+        // the width is detected from the input
+        let input = ripple_carry_adder(13, &[("aau", "z05"), ("aaw", "aba")]);
+        assert_eq!(part2(input), "aau,aaw,aba,z05");
+
+        // two swaps in the same full adder, where neither fixes the bit on its
+        // own
+        let input = ripple_carry_adder(13, &[("aas", "aat"), ("aau", "z05")]);
+        assert_eq!(part2(input), "aas,aat,aau,z05");
+
+        // wider than a u64 can hold
+        let input = ripple_carry_adder(70, &[]);
+        let bits = |pattern: fn(u64) -> u64| {
+            (0..70).fold(BigUint::ZERO, |sum, bit| {
+                sum + (BigUint::from(pattern(bit)) << bit)
+            })
+        };
+        let sum = bits(|bit| bit % 2) + bits(|bit| bit % 3 % 2);
+        assert!(sum.bits() > 64);
+        assert_eq!(part1(input), sum.to_string());
+    }
+
+    #[test]
+    fn export_dot() {
+        use super::to_dot;
+
+        let dot = to_dot(&ripple_carry_adder(4, &[("z02", "aai")]));
+        // four inputs for x and y, and five gates per bit except the first
+        assert_eq!(dot.matches("shape=circle").count(), 8);
+        assert_eq!(dot.matches("shape=box").count(), 17);
+        assert_eq!(dot.matches(" -> ").count(), 34);
+        assert!(dot.contains("\"x01\" -> \"aac\";"));
+        assert!(dot.contains("\"z02\" [shape=box, style=filled, fillcolor=lightblue, label=\"AND\\nz02\", peripheries=2, color=red, penwidth=3];"));
+    }
+
+    #[test]
+    fn export_verilog() {
+        use super::{parse_input, part1, to_verilog};
+        use rustc_hash::FxHashMap;
+
+        // simulates the structural verilog with a tiny interpreter, which
+        // should agree with our own evaluation of the puzzle input
+        let simulate = |input: &str| {
+            let verilog = to_verilog(input);
+            let (wire_values, _) = parse_input(input);
+            let mut values = FxHashMap::default();
+            for line in wire_values.lines() {
+                let (name, value) = line.split_once(": ").unwrap();
+                let name = format!("{}[{}]", &name[..1], name[1..].parse::<usize>().unwrap());
+                values.insert(name, value == "1");
+            }
+            let mut gates = verilog
+                .lines()
+                .filter_map(|line| {
+                    let (primitive, terminals) = line.trim().split_once(" (")?;
+                    let (primitive, _) = primitive.split_once(' ')?;
+                    let terminals = terminals.strip_suffix(");")?.split(", ");
+                    let terminals = terminals.map(str::to_string).collect::<Vec<_>>();
+                    Some((primitive.to_string(), terminals))
+                })
+                .collect::<Vec<_>>();
+            while !gates.is_empty() {
+                gates.retain(|(primitive, terminals)| {
+                    let rhs = terminals.get(2).unwrap_or(&terminals[1]);
+                    let (Some(&a), Some(&b)) = (values.get(&terminals[1]), values.get(rhs)) else {
+                        return true;
+                    };
+                    let out = match primitive.as_str() {
+                        "and" => a & b,
+                        "or" => a | b,
+                        "xor" => a ^ b,
+                        "nand" => !(a & b),
+                        "nor" => !(a | b),
+                        "xnor" => !(a ^ b),
+                        "not" => !a,
+                        _ => unreachable!(),
+                    };
+                    values.insert(terminals[0].clone(), out);
+                    false
+                });
+            }
+            (0..64)
+                .map_while(|bit| values.get(&format!("z[{bit}]")))
+                .enumerate()
+                .map(|(bit, &value)| (value as u64) << bit)
+                .sum::<u64>()
+                .to_string()
+        };
+
+        let input = ripple_carry_adder(16, &[]);
+        assert!(to_verilog(&input).starts_with(
+            "module circuit (
+    input wire [15:0] x,
+    input wire [15:0] y,
+    output wire [16:0] z
+);"
+        ));
+        assert_eq!(simulate(&input), part1(input.clone()));
+
+        let input = ripple_carry_adder(16, &[("z04", "aaq"), ("abh", "abi")]);
+        assert_eq!(simulate(&input), part1(input.clone()));
+    }
+
+    #[test]
+    fn extended_gates() {
+        use super::{part1, to_verilog};
+
+        let input = "x00: 1
+y00: 0
+
+x00 NAND y00 -> z00
+x00 NOR y00 -> z01
+x00 XNOR y00 -> z02
+NOT y00 -> z03
+z00 XNOR z03 -> z04
+NOT z01 -> z05"
+            .to_string();
+        assert_eq!(part1(input.clone()), "57");
+
+        let verilog = to_verilog(&input);
+        assert!(verilog.contains("    nand g0 (z[0], x[0], y[0]);"));
+        assert!(verilog.contains("    not g3 (z[3], y[0]);"));
+    }
+
+    #[test]
+    fn broken_circuits() {
+        use super::{parse_input, Circuit, CircuitError};
+
+        let evaluate = |input: &str| {
+            let (wire_values, gate_connections) = parse_input(input);
+            Circuit::new(&gate_connections).evaluate(wire_values)
+        };
+
+        let input = "x00: 1
+y00: 1
+
+x00 AND abc -> z00
+y00 OR def -> z01
+def XOR abc -> z02";
+        assert_eq!(
+            evaluate(input),
+            Err(CircuitError::UndrivenWires(vec![
+                "abc".to_string(),
+                "def".to_string()
+            ]))
+        );
+
+        let input = "x00: 1
+y00: 1
+
+x00 AND ccc -> aaa
+aaa OR y00 -> bbb
+NOT bbb -> ccc
+bbb XOR x00 -> z00";
+        let error = evaluate(input).unwrap_err();
+        assert_eq!(
+            error,
+            CircuitError::Cycle(vec![
+                "bbb".to_string(),
+                "ccc".to_string(),
+                "aaa".to_string()
+            ])
+        );
+        assert_eq!(
+            error.to_string(),
+            "gates form a loop: bbb -> ccc -> aaa -> bbb"
+        );
+    }
 }
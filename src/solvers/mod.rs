@@ -23,4 +23,5 @@ pub mod day22;
 pub mod day23;
 pub mod day24;
 pub mod day25;
+pub mod params;
 pub mod util;
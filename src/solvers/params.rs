@@ -0,0 +1,87 @@
+use std::{fmt::Display, str::FromStr};
+
+use rustc_hash::FxHashMap;
+
+/// A value the puzzle describes as part of the problem rather than the input,
+/// with the puzzle's value as its default
+#[derive(Debug)]
+pub struct Param {
+    pub key: &'static str,
+    pub default: &'static str,
+    pub help: &'static str,
+    // checks that a value has the type the solver reads the param as
+    pub parse: fn(&str) -> Result<(), String>,
+}
+
+/// Whether a value can be read as a `T`, for checking values given for params
+/// before any solver reads them
+pub fn parses<T: FromStr>(value: &str) -> Result<(), String>
+where
+    T::Err: Display,
+{
+    value.parse::<T>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Values given for parameters, which otherwise keep their defaults
+#[derive(Debug, Default, Clone)]
+pub struct Params {
+    values: FxHashMap<String, String>,
+}
+
+impl Params {
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get<T: FromStr>(&self, param: &Param) -> T
+    where
+        T::Err: Display,
+    {
+        let value = self
+            .values
+            .get(param.key)
+            .map(String::as_str)
+            .unwrap_or(param.default);
+        value
+            .parse()
+            .unwrap_or_else(|e| panic!("invalid value {value} for {}: {e}", param.key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Param = Param {
+        key: "size",
+        default: "71",
+        help: "width and height",
+        parse: parses::<usize>,
+    };
+
+    #[test]
+    fn defaults_and_overrides() {
+        let mut params = Params::default();
+        assert_eq!(params.get::<usize>(&SIZE), 71);
+        params.set("size", "7");
+        assert_eq!(params.get::<usize>(&SIZE), 7);
+    }
+
+    #[test]
+    fn checked_values() {
+        assert_eq!((SIZE.parse)("7"), Ok(()));
+        assert_eq!((SIZE.parse)(SIZE.default), Ok(()));
+        assert_eq!(
+            (SIZE.parse)("-1"),
+            Err("invalid digit found in string".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value seven for size")]
+    fn invalid_value() {
+        let mut params = Params::default();
+        params.set("size", "seven");
+        params.get::<usize>(&SIZE);
+    }
+}
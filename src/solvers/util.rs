@@ -15,17 +15,33 @@ pub fn adjacent_in_bounds(
     });
 }
 
-pub fn get_2d<T>(matrix: &Vec<Vec<T>>, (y, x): (usize, usize)) -> Option<&T> {
-    matrix.get(y).and_then(|row| row.get(x))
+/// A seeded xorshift generator, for when the same pseudo-random numbers are
+/// wanted on every run
+pub struct XorShift(u64);
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // xorshift never leaves zero
+        assert_ne!(seed, 0, "the seed can't be zero");
+        XorShift(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    // a number in 0..n
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
 }
-
-pub fn float_basically_integer(n: f64, threshold_exp: i32) -> Option<u64> {
-    // essentially python math.isclose() which checks if this is basically an integer
-    let rounded = n.round();
-    if (rounded - n).abs() < 10.0f64.powi(threshold_exp) {
-        Some(rounded as u64)
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    // returns (g, s, t) such that a * s + b * t = g = gcd(a, b)
+    if b == 0 {
+        (a, 1, 0)
     } else {
-        None
+        let (g, s, t) = extended_gcd(b, a % b);
+        (g, t, s - (a / b) * t)
     }
 }
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -36,6 +52,29 @@ pub enum Direction {
     Right,
 }
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+    pub fn arrow(&self) -> char {
+        // same symbols as the puzzle inputs use
+        match self {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+        }
+    }
     pub fn apply_unchecked(&self, (y, x): (usize, usize)) -> (usize, usize) {
         // no bounds checking required as the area is padded
         match self {
@@ -46,3 +85,17 @@ impl Direction {
         }
     }
 }
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Direction, String> {
+        // compass points, as the puzzles describe facings
+        match s.to_ascii_lowercase().as_str() {
+            "north" => Ok(Direction::Up),
+            "south" => Ok(Direction::Down),
+            "west" => Ok(Direction::Left),
+            "east" => Ok(Direction::Right),
+            _ => Err(format!("{s} is not north, south, east or west")),
+        }
+    }
+}